@@ -0,0 +1,44 @@
+greeting = Hallo { $username },
+
+welcome-subject = Willkommen, { $username }
+welcome-body = deine E-Mail-Adresse ist bestätigt und dein Konto ist bereit.
+
+email-verification-subject = Bestätige deine E-Mail-Adresse
+email-verification-body = bestätige deine E-Mail-Adresse unter { $link }
+email-verification-action = E-Mail-Adresse bestätigen
+email-verification-expiry = Der Link läuft in { $hours ->
+        [one] einer Stunde
+       *[other] { $hours } Stunden
+    } ab.
+
+password-reset-subject = Setze dein Passwort zurück
+password-reset-body = setze dein Passwort unter { $link } zurück
+password-reset-action = Passwort zurücksetzen
+password-reset-expiry = Der Link läuft in { $minutes ->
+        [one] einer Minute
+       *[other] { $minutes } Minuten
+    } ab. Wenn du kein neues Passwort angefordert hast, kannst du diese E-Mail ignorieren.
+
+password-changed-subject = Dein Passwort wurde geändert
+password-changed-body = das Passwort deines Kontos wurde gerade geändert und deine anderen Sitzungen wurden abgemeldet. Wenn du das nicht warst, setze dein Passwort sofort zurück.
+
+email-change-confirm-subject = Bestätige deine neue E-Mail-Adresse
+email-change-confirm-body = bestätige { $new_email } als neue E-Mail-Adresse deines Kontos unter { $link }
+email-change-confirm-intro = bestätige { $new_email } als neue E-Mail-Adresse deines Kontos.
+email-change-confirm-action = { $new_email } bestätigen
+
+email-change-notice-subject = Deine E-Mail-Adresse wird geändert
+email-change-notice-body = für dein Konto wurde eine Änderung der E-Mail-Adresse zu { $new_email } angefordert. Wenn du das nicht warst, brich sie unter { $link } ab.
+email-change-notice-intro = für dein Konto wurde eine Änderung der E-Mail-Adresse zu { $new_email } angefordert.
+email-change-notice-action = Nicht du? Änderung abbrechen
+
+new-login-subject = Neue Anmeldung bei deinem Konto
+new-login-body = bei deinem Konto hat sich am { $signed_in_at } UTC { $device } angemeldet.
+new-login-body-with-ip = bei deinem Konto hat sich am { $signed_in_at } UTC { $device } ({ $ip_address }) angemeldet.
+new-login-advice = Wenn du das nicht warst, ändere dein Passwort und melde deine anderen Sitzungen ab.
+
+account-deleted-subject = Dein Konto wurde gelöscht
+account-deleted-body = dein Konto wurde gelöscht. Melde dich innerhalb { $days ->
+        [one] eines Tages
+       *[other] von { $days } Tagen
+    } an, um es wiederherzustellen, danach wird es endgültig entfernt.
@@ -0,0 +1,23 @@
+identifier_required = Gib deinen Benutzernamen oder deine E-Mail-Adresse ein.
+username_required = Wähle einen Benutzernamen.
+username_length_invalid = Benutzernamen brauchen mindestens 4 Zeichen.
+username_exists = Dieser Benutzername ist vergeben.
+username_change_too_soon = Du hast deinen Benutzernamen vor Kurzem geändert, versuche es später erneut.
+email_required = Gib deine E-Mail-Adresse ein.
+email_invalid = Gib eine gültige E-Mail-Adresse ein.
+email_exists = Es gibt bereits ein Konto mit dieser E-Mail-Adresse.
+email_not_verified = Bestätige deine E-Mail-Adresse, bevor du dich anmeldest.
+password_required = Gib ein Passwort ein.
+password_repeat_required = Wiederhole das Passwort.
+password_length_invalid = Passwörter brauchen mindestens 12 Zeichen.
+password_not_matching = Die Passwörter stimmen nicht überein.
+password_invalid = Das Passwort ist falsch.
+current_password_required = Gib dein aktuelles Passwort ein.
+current_password_invalid = Dein aktuelles Passwort ist falsch.
+token_required = Der Link ist unvollständig.
+token_invalid = Dieser Link ist ungültig oder abgelaufen.
+twitch_email_unavailable = Dein Twitch-Konto hat keine E-Mail-Adresse, die wir verwenden können.
+twitch_link_requires_login = Melde dich bei dem Konto mit dieser E-Mail-Adresse an, um dein Twitch-Konto damit zu verknüpfen.
+locale_unsupported = Diese Sprache wird nicht unterstützt.
+status_invalid = Unbekannter Status.
+export_failed = Der Export konnte nicht erstellt werden, fordere einen neuen an.
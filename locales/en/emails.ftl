@@ -0,0 +1,44 @@
+greeting = Hi { $username },
+
+welcome-subject = Welcome, { $username }
+welcome-body = your email address is confirmed and your account is ready to use.
+
+email-verification-subject = Confirm your email address
+email-verification-body = confirm your email address with { $link }
+email-verification-action = Confirm your email address
+email-verification-expiry = It expires in { $hours ->
+        [one] { $hours } hour
+       *[other] { $hours } hours
+    }.
+
+password-reset-subject = Reset your password
+password-reset-body = reset your password with { $link }
+password-reset-action = Reset your password
+password-reset-expiry = It expires in { $minutes ->
+        [one] { $minutes } minute
+       *[other] { $minutes } minutes
+    }. If you did not ask for a password reset you can ignore this email.
+
+password-changed-subject = Your password was changed
+password-changed-body = the password of your account was just changed and your other sessions were signed out. If this was not you, reset your password right away.
+
+email-change-confirm-subject = Confirm your new email address
+email-change-confirm-body = confirm { $new_email } as the new email address of your account with { $link }
+email-change-confirm-intro = confirm { $new_email } as the new email address of your account.
+email-change-confirm-action = Confirm { $new_email }
+
+email-change-notice-subject = Your email address is being changed
+email-change-notice-body = a change of your account's email address to { $new_email } was requested. If this was not you, cancel it with { $link }
+email-change-notice-intro = a change of your account's email address to { $new_email } was requested.
+email-change-notice-action = Not you? Cancel the change
+
+new-login-subject = New sign-in to your account
+new-login-body = your account was signed in to from { $device } at { $signed_in_at } UTC.
+new-login-body-with-ip = your account was signed in to from { $device } ({ $ip_address }) at { $signed_in_at } UTC.
+new-login-advice = If this was not you, change your password and sign out your other sessions.
+
+account-deleted-subject = Your account was deleted
+account-deleted-body = your account was deleted. Sign in within { $days ->
+        [one] { $days } day
+       *[other] { $days } days
+    } to restore it, after that it is removed for good.
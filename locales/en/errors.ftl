@@ -0,0 +1,23 @@
+identifier_required = Enter your username or email address.
+username_required = Choose a username.
+username_length_invalid = Usernames need at least 4 characters.
+username_exists = This username is taken.
+username_change_too_soon = You changed your username recently, try again later.
+email_required = Enter your email address.
+email_invalid = Enter a valid email address.
+email_exists = An account with this email address already exists.
+email_not_verified = Confirm your email address before signing in.
+password_required = Enter a password.
+password_repeat_required = Repeat the password.
+password_length_invalid = Passwords need at least 12 characters.
+password_not_matching = The passwords do not match.
+password_invalid = The password is wrong.
+current_password_required = Enter your current password.
+current_password_invalid = Your current password is wrong.
+token_required = The link is incomplete.
+token_invalid = This link is invalid or has expired.
+twitch_email_unavailable = Your Twitch account has no email address we could use.
+twitch_link_requires_login = Sign in to the account with this email address to link your Twitch account to it.
+locale_unsupported = This language is not supported.
+status_invalid = Unknown status.
+export_failed = The export could not be created, request a new one.
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE revoked_tokens (
+    id SERIAL PRIMARY KEY,
+    jti VARCHAR NOT NULL UNIQUE,
+    expiry TIMESTAMP NOT NULL
+);
+
+CREATE INDEX revoked_tokens_expiry_idx ON revoked_tokens (expiry);
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE security_events;
+
+ALTER TABLE refresh_tokens
+    DROP COLUMN family_id,
+    DROP COLUMN parent_id,
+    DROP COLUMN rotated_at;
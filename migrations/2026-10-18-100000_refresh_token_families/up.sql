@@ -0,0 +1,21 @@
+-- Your SQL goes here
+ALTER TABLE refresh_tokens
+    ADD COLUMN family_id VARCHAR,
+    ADD COLUMN parent_id INTEGER REFERENCES refresh_tokens(id) ON DELETE SET NULL,
+    ADD COLUMN rotated_at TIMESTAMP;
+
+UPDATE refresh_tokens SET family_id = 'legacy-' || id;
+
+ALTER TABLE refresh_tokens ALTER COLUMN family_id SET NOT NULL;
+
+CREATE INDEX refresh_tokens_family_id_idx ON refresh_tokens (family_id);
+
+CREATE TABLE security_events (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id),
+    event_type VARCHAR NOT NULL,
+    detail TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX security_events_user_id_idx ON security_events (user_id);
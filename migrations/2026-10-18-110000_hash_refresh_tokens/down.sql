@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX refresh_tokens_token_hash_idx;
+
+DELETE FROM refresh_tokens;
+
+ALTER TABLE refresh_tokens RENAME COLUMN token_hash TO token;
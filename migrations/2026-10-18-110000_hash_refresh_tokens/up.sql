@@ -0,0 +1,8 @@
+-- Your SQL goes here
+-- Existing rows hold plaintext tokens that cannot be hashed with the application
+-- key from SQL, so they are invalidated and those sessions have to log in again.
+DELETE FROM refresh_tokens;
+
+ALTER TABLE refresh_tokens RENAME COLUMN token TO token_hash;
+
+CREATE UNIQUE INDEX refresh_tokens_token_hash_idx ON refresh_tokens (token_hash);
@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX refresh_tokens_user_id_idx;
+
+ALTER TABLE refresh_tokens
+    DROP COLUMN user_agent,
+    DROP COLUMN ip_address,
+    DROP COLUMN label,
+    DROP COLUMN created_at,
+    DROP COLUMN last_used_at;
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+ALTER TABLE refresh_tokens
+    ADD COLUMN user_agent VARCHAR,
+    ADD COLUMN ip_address VARCHAR,
+    ADD COLUMN label VARCHAR NOT NULL DEFAULT 'Unknown device',
+    ADD COLUMN created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ADD COLUMN last_used_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP;
+
+CREATE INDEX refresh_tokens_user_id_idx ON refresh_tokens (user_id);
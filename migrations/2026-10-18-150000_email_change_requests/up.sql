@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE email_change_requests (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL UNIQUE REFERENCES users(id) ON DELETE CASCADE,
+    new_email TEXT NOT NULL,
+    confirm_token_hash VARCHAR NOT NULL UNIQUE,
+    cancel_token_hash VARCHAR NOT NULL UNIQUE,
+    expiry TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
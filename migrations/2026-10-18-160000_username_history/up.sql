@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE username_history (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    username TEXT NOT NULL,
+    changed_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    held_until TIMESTAMP NOT NULL
+);
+
+CREATE INDEX username_history_user_id_idx ON username_history (user_id);
+CREATE INDEX username_history_username_idx ON username_history (username);
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+ALTER TABLE users ADD COLUMN deleted_at TIMESTAMP;
+
+UPDATE users SET deleted_at = updated_at WHERE is_deleted;
+
+CREATE INDEX users_deleted_at_idx ON users (deleted_at) WHERE is_deleted;
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+CREATE TABLE account_tombstones (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL UNIQUE,
+    deleted_at TIMESTAMP,
+    purged_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
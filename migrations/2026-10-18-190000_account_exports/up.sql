@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE account_exports (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    token_hash VARCHAR NOT NULL UNIQUE,
+    archive TEXT,
+    expiry TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    completed_at TIMESTAMP,
+    failed_at TIMESTAMP
+);
+
+CREATE INDEX account_exports_user_id_idx ON account_exports (user_id);
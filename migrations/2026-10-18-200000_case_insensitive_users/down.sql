@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX username_history_username_lower_idx;
+
+DROP INDEX users_email_lower_key;
+DROP INDEX users_username_lower_key;
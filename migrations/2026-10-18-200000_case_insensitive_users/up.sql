@@ -0,0 +1,41 @@
+-- Your SQL goes here
+
+-- Accounts that differ only in case (or surrounding whitespace of the email) cannot be
+-- merged automatically. List them so they can be renamed or merged before retrying:
+--   SELECT lower(trim(email)), array_agg(id) FROM users GROUP BY 1 HAVING count(*) > 1;
+--   SELECT lower(username), array_agg(id) FROM users GROUP BY 1 HAVING count(*) > 1;
+DO $$
+DECLARE
+    conflicts TEXT;
+BEGIN
+    SELECT string_agg(format('%s (ids %s)', email, ids), ', ') INTO conflicts
+    FROM (
+        SELECT lower(trim(email)) AS email, array_agg(id ORDER BY id) AS ids
+        FROM users
+        GROUP BY 1
+        HAVING count(*) > 1
+    ) duplicates;
+    IF conflicts IS NOT NULL THEN
+        RAISE EXCEPTION 'users with case-insensitively equal emails: %', conflicts
+            USING HINT = 'Change or merge these accounts, then run the migration again.';
+    END IF;
+
+    SELECT string_agg(format('%s (ids %s)', username, ids), ', ') INTO conflicts
+    FROM (
+        SELECT lower(username) AS username, array_agg(id ORDER BY id) AS ids
+        FROM users
+        GROUP BY 1
+        HAVING count(*) > 1
+    ) duplicates;
+    IF conflicts IS NOT NULL THEN
+        RAISE EXCEPTION 'users with case-insensitively equal usernames: %', conflicts
+            USING HINT = 'Rename or merge these accounts, then run the migration again.';
+    END IF;
+END $$;
+
+UPDATE users SET email = lower(trim(email)) WHERE email <> lower(trim(email));
+
+CREATE UNIQUE INDEX users_username_lower_key ON users (lower(username));
+CREATE UNIQUE INDEX users_email_lower_key ON users (lower(email));
+
+CREATE INDEX username_history_username_lower_idx ON username_history (lower(username));
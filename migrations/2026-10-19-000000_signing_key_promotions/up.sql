@@ -0,0 +1,6 @@
+-- Your SQL goes here
+CREATE TABLE signing_key_promotions (
+    id SERIAL PRIMARY KEY,
+    kid TEXT NOT NULL,
+    promoted_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -2,3 +2,5 @@ use rocket_sync_db_pools::{database, diesel::PgConnection};
 
 #[database("pg_conn")]
 pub struct DbConn(PgConnection);
+
+sql_function!(fn lower(x: diesel::sql_types::Text) -> diesel::sql_types::Text);
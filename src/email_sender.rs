@@ -1,29 +1,327 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
 use lettre::{
+    message::MultiPart,
     transport::smtp::authentication::{Credentials, Mechanism},
     AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
 };
+use rocket::{
+    error,
+    http::Status,
+    request::{FromRequest, Outcome},
+    tokio,
+};
+
+use crate::{
+    email_templates::{EmailContext, EmailTemplates},
+    i18n::Catalogs,
+    models::email_outbox::{NewOutboxEmail, OutboxEmail},
+    util::{
+        globals::{EmailConfig, EmailTransportKind, SmtpTls},
+        locale::Locale,
+        outbox_cipher::OutboxCipher,
+        random::random_id,
+    },
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+    pub html: Option<String>,
+}
+
+impl Email {
+    /// Decrypts the sealed body of a queued email.
+    pub fn open(email: OutboxEmail, cipher: &OutboxCipher) -> Result<Self, EmailError> {
+        let html = match email.html {
+            Some(html) => Some(cipher.open(&html).ok_or(EmailError::Sealed)?),
+            None => None,
+        };
+
+        Ok(Self {
+            to: email.recipient,
+            subject: email.subject,
+            body: cipher.open(&email.body).ok_or(EmailError::Sealed)?,
+            html,
+        })
+    }
+
+    fn message(&self, from: &str) -> Result<Message, EmailError> {
+        let builder = Message::builder()
+            .from(from.parse().map_err(EmailError::Address)?)
+            .to(self.to.parse().map_err(EmailError::Address)?)
+            .subject(self.subject.clone());
+
+        match &self.html {
+            Some(html) => builder.multipart(MultiPart::alternative_plain_html(
+                self.body.clone(),
+                html.clone(),
+            )),
+            None => builder.body(self.body.clone()),
+        }
+        .map_err(EmailError::Message)
+    }
+}
+
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum EmailError {
+    Address(lettre::address::AddressError),
+    Message(lettre::error::Error),
+    Smtp(lettre::transport::smtp::Error),
+    Io(std::io::Error),
+    Template(tera::Error),
+    /// The queued body could not be decrypted with the configured key.
+    Sealed,
+}
+
+#[async_trait]
+pub trait EmailTransport: Send + Sync {
+    async fn send(&self, email: &Email) -> Result<(), EmailError>;
+}
+
+pub struct SmtpTransport {
+    from: String,
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpTransport {
+    pub fn from(config: &EmailConfig) -> Result<Self, EmailError> {
+        let host = config.email_smtp_host.as_str();
+        let builder = match config.email_smtp_tls {
+            SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+                .map_err(EmailError::Smtp)?,
+            SmtpTls::Tls => {
+                AsyncSmtpTransport::<Tokio1Executor>::relay(host).map_err(EmailError::Smtp)?
+            }
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+        };
+        let builder = match config.email_username.is_empty() {
+            true => builder,
+            false => builder
+                .credentials(Credentials::new(
+                    config.email_username.clone(),
+                    config.email_password.clone(),
+                ))
+                .authentication(vec![Mechanism::Plain]),
+        };
+
+        Ok(Self {
+            from: config.sender().to_owned(),
+            mailer: builder.port(config.smtp_port()).build(),
+        })
+    }
+}
+
+#[async_trait]
+impl EmailTransport for SmtpTransport {
+    async fn send(&self, email: &Email) -> Result<(), EmailError> {
+        self.mailer
+            .send(email.message(&self.from)?)
+            .await
+            .map(|_| ())
+            .map_err(EmailError::Smtp)
+    }
+}
+
+/// Writes every message to its own `.eml` file instead of delivering it.
+pub struct FileTransport {
+    from: String,
+    dir: PathBuf,
+}
+
+impl FileTransport {
+    pub fn from(config: &EmailConfig) -> Self {
+        Self {
+            from: config.sender().to_owned(),
+            dir: PathBuf::from(&config.email_file_dir),
+        }
+    }
+}
+
+#[async_trait]
+impl EmailTransport for FileTransport {
+    async fn send(&self, email: &Email) -> Result<(), EmailError> {
+        let message = email.message(&self.from)?;
+        let file_name = format!(
+            "{}-{}.eml",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S"),
+            random_id()
+        );
+
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(EmailError::Io)?;
+        tokio::fs::write(self.dir.join(file_name), message.formatted())
+            .await
+            .map_err(EmailError::Io)
+    }
+}
+
+/// Collects messages in memory so tests can assert on what was sent.
+#[derive(Clone, Default)]
+pub struct MemoryTransport {
+    sent: Arc<Mutex<Vec<Email>>>,
+}
+
+impl MemoryTransport {
+    pub fn sent(&self) -> Vec<Email> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl EmailTransport for MemoryTransport {
+    async fn send(&self, email: &Email) -> Result<(), EmailError> {
+        self.sent.lock().unwrap().push(email.clone());
+        Ok(())
+    }
+}
+
+/// The configured transport and templates, managed as state. Emails are rendered into the
+/// outbox and delivered by the `email_delivery` job.
+#[derive(Clone)]
+pub struct Mailer {
+    transport: Arc<dyn EmailTransport>,
+    templates: Arc<EmailTemplates>,
+    cipher: OutboxCipher,
+    memory: Option<MemoryTransport>,
+    enabled: bool,
+}
+
+impl Mailer {
+    pub fn from(
+        config: &EmailConfig,
+        catalogs: Catalogs,
+        cipher: OutboxCipher,
+    ) -> Result<Self, EmailError> {
+        let (transport, memory): (Arc<dyn EmailTransport>, _) = match config.email_transport {
+            EmailTransportKind::Smtp => (Arc::new(SmtpTransport::from(config)?), None),
+            EmailTransportKind::File => (Arc::new(FileTransport::from(config)), None),
+            EmailTransportKind::Memory => {
+                let memory = MemoryTransport::default();
+                (Arc::new(memory.clone()), Some(memory))
+            }
+        };
+
+        let templates = EmailTemplates::load(&config.email_template_dir, catalogs)
+            .map_err(EmailError::Template)?;
+
+        Ok(Self {
+            transport,
+            templates: Arc::new(templates),
+            cipher,
+            memory,
+            enabled: config.email_enabled,
+        })
+    }
+
+    pub fn templates(&self) -> &EmailTemplates {
+        &self.templates
+    }
+
+    pub fn transport(&self) -> Arc<dyn EmailTransport> {
+        self.transport.clone()
+    }
+
+    pub fn cipher(&self) -> OutboxCipher {
+        self.cipher.clone()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Renders the template of `context` for the outbox, with the body sealed. `None` when
+    /// `email_enabled` is off or the template fails to render.
+    pub fn render<T: EmailContext>(
+        &self,
+        user_id: i32,
+        to: String,
+        locale: Locale,
+        context: &T,
+    ) -> Option<NewOutboxEmail> {
+        if !self.enabled {
+            return None;
+        }
+
+        match self.templates.render(context, locale) {
+            Ok(rendered) => Some(NewOutboxEmail {
+                user_id,
+                recipient: to,
+                subject: rendered.subject,
+                body: self.cipher.seal(&rendered.text),
+                html: Some(self.cipher.seal(&rendered.html)),
+            }),
+            Err(e) => {
+                error!("Failed to render email {}: {:?}", T::TEMPLATE, e);
+                None
+            }
+        }
+    }
+
+    /// Messages sent so far by the in-memory transport, empty for other transports.
+    #[allow(dead_code)]
+    pub fn sent(&self) -> Vec<Email> {
+        self.memory
+            .as_ref()
+            .map(|memory| memory.sent())
+            .unwrap_or_default()
+    }
+}
+
+/// The managed `Mailer` with the locale negotiated from the request's `Accept-Language`.
+pub struct LocalizedMailer<'r> {
+    mailer: &'r Mailer,
+    locale: Locale,
+}
+
+impl LocalizedMailer<'_> {
+    pub fn mailer(&self) -> &Mailer {
+        self.mailer
+    }
+
+    pub fn locale(&self) -> Locale {
+        self.locale
+    }
+
+    /// Renders in the recipient's stored `preference` when there is one.
+    pub fn render<T: EmailContext>(
+        &self,
+        user_id: i32,
+        to: String,
+        preference: Option<&str>,
+        context: &T,
+    ) -> Option<NewOutboxEmail> {
+        self.mailer
+            .render(user_id, to, self.locale.or_preference(preference), context)
+    }
+}
+
+#[async_trait]
+impl<'r> FromRequest<'r> for LocalizedMailer<'r> {
+    type Error = ();
+
+    async fn from_request(request: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        let locale = Locale::negotiate(request.headers().get_one("Accept-Language"));
+
+        match request.rocket().state::<Mailer>() {
+            Some(mailer) => Outcome::Success(LocalizedMailer { mailer, locale }),
+            None => Outcome::Failure((Status::InternalServerError, ())),
+        }
+    }
+}
 
-pub async fn send_email(to: String, email_username: String, email_password: String) {
-    let email = Message::builder()
-        .from(
-            "Ibrahim Mahmood <ibrahimpmahmood@gmail.com>"
-                .parse()
-                .unwrap(),
-        )
-        .to(to.parse().unwrap())
-        .subject("Happy new year")
-        .body("Be happy!".to_owned())
-        .unwrap();
-
-    let async_mailer: AsyncSmtpTransport<Tokio1Executor> =
-        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay("smtp.gmail.com")
-            .unwrap()
-            .credentials(Credentials::new(email_username, email_password))
-            .authentication(vec![Mechanism::Plain])
-            .build();
-
-    match async_mailer.send(email).await {
-        Ok(_) => println!("Email sent"),
-        Err(e) => println!("Failed to send email {:?}", e),
+/// Link to the page that redeems `token`, or the bare token when no page is configured.
+pub fn token_link(url: Option<&String>, token: &str) -> String {
+    match url {
+        Some(url) => format!("{}?token={}", url, token),
+        None => token.to_owned(),
     }
 }
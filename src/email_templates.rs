@@ -0,0 +1,253 @@
+use serde::Serialize;
+use tera::{Context, Tera};
+
+use crate::{
+    i18n::{Catalogs, Translate},
+    util::locale::Locale,
+};
+
+/// Templates compiled into the binary. A directory configured with `email_template_dir`
+/// may override any of them using the same `<template>/<part>` layout. Their text comes
+/// from the message catalogs through `t`.
+macro_rules! builtin_templates {
+    ($($name:literal),* $(,)?) => {
+        &[$(
+            (concat!($name, "/subject.txt"), include_str!(concat!("../templates/email/", $name, "/subject.txt"))),
+            (concat!($name, "/body.txt"), include_str!(concat!("../templates/email/", $name, "/body.txt"))),
+            (concat!($name, "/body.html"), include_str!(concat!("../templates/email/", $name, "/body.html"))),
+        )*]
+    };
+}
+
+const BUILTIN_TEMPLATES: &[(&str, &str)] = builtin_templates!(
+    "welcome",
+    "email_verification",
+    "password_reset",
+    "password_changed",
+    "email_change_confirm",
+    "email_change_notice",
+    "new_login",
+    "account_deleted",
+);
+
+/// Context of one email template. `sample` fills the admin preview.
+pub trait EmailContext: Serialize {
+    const TEMPLATE: &'static str;
+
+    fn sample() -> Self;
+}
+
+#[derive(Debug, Serialize)]
+pub struct RenderedEmail {
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+pub struct EmailTemplates {
+    tera: Tera,
+}
+
+impl EmailTemplates {
+    pub fn load(dir: &str, catalogs: Catalogs) -> Result<Self, tera::Error> {
+        let mut tera = match std::path::Path::new(dir).is_dir() {
+            true => Tera::new(&format!("{}/**/*", dir))?,
+            false => Tera::default(),
+        };
+
+        let mut builtin = Tera::default();
+        builtin.add_raw_templates(BUILTIN_TEMPLATES.to_vec())?;
+        tera.extend(&builtin)?;
+        tera.register_function("t", Translate(catalogs));
+
+        Ok(Self { tera })
+    }
+
+    pub fn render<T: EmailContext>(
+        &self,
+        context: &T,
+        locale: Locale,
+    ) -> Result<RenderedEmail, tera::Error> {
+        let mut context = Context::from_serialize(context)?;
+        context.insert("locale", locale.as_str());
+        let render = |part: &str| {
+            self.tera
+                .render(&format!("{}/{}", T::TEMPLATE, part), &context)
+        };
+
+        Ok(RenderedEmail {
+            subject: render("subject.txt")?.trim().to_owned(),
+            text: render("body.txt")?,
+            html: render("body.html")?,
+        })
+    }
+
+    /// Renders the template called `name` with its sample context.
+    pub fn preview(
+        &self,
+        name: &str,
+        locale: Locale,
+    ) -> Option<Result<RenderedEmail, tera::Error>> {
+        Some(match name {
+            WelcomeEmail::TEMPLATE => self.render(&WelcomeEmail::sample(), locale),
+            VerificationEmail::TEMPLATE => self.render(&VerificationEmail::sample(), locale),
+            PasswordResetEmail::TEMPLATE => self.render(&PasswordResetEmail::sample(), locale),
+            PasswordChangedEmail::TEMPLATE => self.render(&PasswordChangedEmail::sample(), locale),
+            EmailChangeConfirmEmail::TEMPLATE => {
+                self.render(&EmailChangeConfirmEmail::sample(), locale)
+            }
+            EmailChangeNoticeEmail::TEMPLATE => {
+                self.render(&EmailChangeNoticeEmail::sample(), locale)
+            }
+            NewLoginEmail::TEMPLATE => self.render(&NewLoginEmail::sample(), locale),
+            AccountDeletedEmail::TEMPLATE => self.render(&AccountDeletedEmail::sample(), locale),
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct WelcomeEmail {
+    pub username: String,
+}
+
+impl EmailContext for WelcomeEmail {
+    const TEMPLATE: &'static str = "welcome";
+
+    fn sample() -> Self {
+        Self {
+            username: "ibrahim".to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct VerificationEmail {
+    pub username: String,
+    pub link: String,
+    pub expires_in_hours: i64,
+}
+
+impl EmailContext for VerificationEmail {
+    const TEMPLATE: &'static str = "email_verification";
+
+    fn sample() -> Self {
+        Self {
+            username: "ibrahim".to_owned(),
+            link: "https://example.com/verify?token=sample".to_owned(),
+            expires_in_hours: 24,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PasswordResetEmail {
+    pub username: String,
+    pub link: String,
+    pub expires_in_minutes: i64,
+}
+
+impl EmailContext for PasswordResetEmail {
+    const TEMPLATE: &'static str = "password_reset";
+
+    fn sample() -> Self {
+        Self {
+            username: "ibrahim".to_owned(),
+            link: "https://example.com/reset?token=sample".to_owned(),
+            expires_in_minutes: 60,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PasswordChangedEmail {
+    pub username: String,
+}
+
+impl EmailContext for PasswordChangedEmail {
+    const TEMPLATE: &'static str = "password_changed";
+
+    fn sample() -> Self {
+        Self {
+            username: "ibrahim".to_owned(),
+        }
+    }
+}
+
+/// Sent to the new address of a pending email change.
+#[derive(Debug, Serialize)]
+pub struct EmailChangeConfirmEmail {
+    pub username: String,
+    pub new_email: String,
+    pub link: String,
+}
+
+impl EmailContext for EmailChangeConfirmEmail {
+    const TEMPLATE: &'static str = "email_change_confirm";
+
+    fn sample() -> Self {
+        Self {
+            username: "ibrahim".to_owned(),
+            new_email: "new@example.com".to_owned(),
+            link: "https://example.com/email/confirm?token=sample".to_owned(),
+        }
+    }
+}
+
+/// Sent to the current address of a pending email change.
+#[derive(Debug, Serialize)]
+pub struct EmailChangeNoticeEmail {
+    pub username: String,
+    pub new_email: String,
+    pub link: String,
+}
+
+impl EmailContext for EmailChangeNoticeEmail {
+    const TEMPLATE: &'static str = "email_change_notice";
+
+    fn sample() -> Self {
+        Self {
+            username: "ibrahim".to_owned(),
+            new_email: "new@example.com".to_owned(),
+            link: "https://example.com/email/cancel?token=sample".to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct NewLoginEmail {
+    pub username: String,
+    pub device: String,
+    pub ip_address: Option<String>,
+    pub signed_in_at: String,
+}
+
+impl EmailContext for NewLoginEmail {
+    const TEMPLATE: &'static str = "new_login";
+
+    fn sample() -> Self {
+        Self {
+            username: "ibrahim".to_owned(),
+            device: "Firefox on Linux".to_owned(),
+            ip_address: Some("203.0.113.7".to_owned()),
+            signed_in_at: "2021-01-01 12:00".to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccountDeletedEmail {
+    pub username: String,
+    pub grace_period_days: i64,
+}
+
+impl EmailContext for AccountDeletedEmail {
+    const TEMPLATE: &'static str = "account_deleted";
+
+    fn sample() -> Self {
+        Self {
+            username: "ibrahim".to_owned(),
+            grace_period_days: 30,
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::{collections::HashMap, sync::Arc};
+
+use fluent_bundle::{
+    concurrent::FluentBundle, FluentArgs, FluentError, FluentResource, FluentValue,
+};
+use tera::Value;
+
+use crate::util::locale::{Locale, DEFAULT_LOCALE};
+
+/// Message catalogs compiled into the binary, one per supported locale. Email templates
+/// look their text up with `t`, error responses translate their codes with them.
+macro_rules! builtin_catalogs {
+    ($($locale:literal),* $(,)?) => {
+        &[$(
+            ($locale, &[
+                include_str!(concat!("../locales/", $locale, "/emails.ftl")),
+                include_str!(concat!("../locales/", $locale, "/errors.ftl")),
+            ]),
+        )*]
+    };
+}
+
+pub const BUILTIN_CATALOGS: &[(&str, &[&str])] = builtin_catalogs!("en", "de");
+
+/// A builtin catalog that failed to parse, with the locale it belongs to.
+#[derive(Debug)]
+#[allow(dead_code)]
+pub struct CatalogError(&'static str, Vec<FluentError>);
+
+#[derive(Clone)]
+pub struct Catalogs {
+    bundles: Arc<HashMap<&'static str, FluentBundle<FluentResource>>>,
+}
+
+impl Catalogs {
+    pub fn load() -> Result<Self, CatalogError> {
+        let mut bundles = HashMap::new();
+
+        for (locale, sources) in BUILTIN_CATALOGS {
+            let mut bundle = FluentBundle::new_concurrent(vec![locale.parse().unwrap()]);
+            // Isolation marks around placeables would end up in plain text emails.
+            bundle.set_use_isolating(false);
+
+            for source in sources.iter() {
+                let resource = FluentResource::try_new(source.to_string()).map_err(|(_, e)| {
+                    CatalogError(
+                        locale,
+                        e.into_iter().map(FluentError::ParserError).collect(),
+                    )
+                })?;
+                bundle
+                    .add_resource(resource)
+                    .map_err(|e| CatalogError(locale, e))?;
+            }
+
+            bundles.insert(*locale, bundle);
+        }
+
+        Ok(Self {
+            bundles: Arc::new(bundles),
+        })
+    }
+
+    /// Formats message `key` in `locale`, falling back to the default locale when the
+    /// catalog of `locale` lacks it.
+    pub fn message(&self, locale: Locale, key: &str, args: Option<&FluentArgs>) -> Option<String> {
+        [locale.as_str(), DEFAULT_LOCALE]
+            .iter()
+            .filter_map(|locale| self.bundles.get(locale))
+            .find_map(|bundle| {
+                let pattern = bundle.get_message(key)?.value()?;
+                let mut errors = vec![];
+                let message = bundle.format_pattern(pattern, args, &mut errors);
+
+                match errors.is_empty() {
+                    true => Some(message.into_owned()),
+                    false => None,
+                }
+            })
+    }
+
+    /// Human readable messages for error codes; codes without one are passed through.
+    pub fn error_messages(&self, locale: Locale, codes: &[String]) -> Vec<String> {
+        codes
+            .iter()
+            .map(|code| {
+                self.message(locale, code, None)
+                    .unwrap_or_else(|| code.to_owned())
+            })
+            .collect()
+    }
+}
+
+/// The `t` function of email templates: `{{ t(key="greeting", locale=locale,
+/// username=username) }}`. Arguments besides `key` and `locale` become message arguments.
+pub struct Translate(pub Catalogs);
+
+impl tera::Function for Translate {
+    fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let key = match args.get("key") {
+            Some(Value::String(key)) => key,
+            _ => return Err(tera::Error::msg("t requires a `key`")),
+        };
+        let locale = args
+            .get("locale")
+            .and_then(Value::as_str)
+            .and_then(Locale::parse)
+            .unwrap_or_default();
+
+        let mut message_args = FluentArgs::new();
+        for (name, value) in args
+            .iter()
+            .filter(|(name, _)| *name != "key" && *name != "locale")
+        {
+            let value = match value {
+                Value::String(s) => FluentValue::from(s.as_str()),
+                Value::Number(n) if n.is_i64() => FluentValue::from(n.as_i64().unwrap()),
+                Value::Number(n) => FluentValue::from(n.as_f64().unwrap_or_default()),
+                other => FluentValue::from(other.to_string()),
+            };
+            message_args.set(name.as_str(), value);
+        }
+
+        self.0
+            .message(locale, key, Some(&message_args))
+            .map(Value::String)
+            .ok_or_else(|| tera::Error::msg(format!("no message `{}`", key)))
+    }
+}
@@ -0,0 +1,362 @@
+use crate::{
+    database::DbConn,
+    email_sender::{Email, EmailTransport, Mailer},
+    jwt::KeyRing,
+    repository::{
+        account_export, email_outbox, refresh_token, revoked_token, signing_key_promotion, user,
+    },
+    util::{
+        globals::{CleanupConfig, JWTConfig, OutboxConfig},
+        outbox_cipher::OutboxCipher,
+    },
+};
+use diesel::{pg::PgConnection, QueryResult};
+use futures::Future;
+use rocket::{
+    error, fairing::AdHoc, info, tokio, tokio::time::Duration, warn, Build, Orbit, Rocket,
+};
+use rocket_sync_db_pools::ConnectionPool;
+use std::sync::Arc;
+
+/// Rotated tokens younger than this are never purged so a refresh in flight can still
+/// insert its successor.
+const ROTATION_GRACE_SECONDS: i64 = 60;
+
+/// Claimed emails are skipped by other workers for this long, after which a worker that
+/// died mid-delivery no longer holds them.
+const OUTBOX_LEASE_SECONDS: i64 = 300;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct CleanupReport {
+    pub expired_refresh_tokens: usize,
+    pub revoked_refresh_tokens: usize,
+    pub expired_revoked_tokens: usize,
+    pub expired_account_exports: usize,
+}
+
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum JobError {
+    Query(diesel::result::Error),
+}
+
+fn in_batches(
+    batch_size: i64,
+    mut delete_batch: impl FnMut() -> QueryResult<usize>,
+) -> QueryResult<usize> {
+    let mut total = 0;
+    loop {
+        let deleted = delete_batch()?;
+        total += deleted;
+        if (deleted as i64) < batch_size {
+            return Ok(total);
+        }
+    }
+}
+
+pub fn purge_tokens(conn: &PgConnection, batch_size: i64) -> QueryResult<CleanupReport> {
+    let now = chrono::Utc::now().naive_utc();
+    let rotated_before = now - chrono::Duration::seconds(ROTATION_GRACE_SECONDS);
+
+    Ok(CleanupReport {
+        expired_refresh_tokens: in_batches(batch_size, || {
+            refresh_token::delete_expired_batch(conn, now, batch_size)
+        })?,
+        revoked_refresh_tokens: in_batches(batch_size, || {
+            refresh_token::delete_revoked_batch(conn, rotated_before, batch_size)
+        })?,
+        expired_revoked_tokens: in_batches(batch_size, || {
+            revoked_token::delete_expired_batch(conn, now, batch_size)
+        })?,
+        expired_account_exports: in_batches(batch_size, || {
+            account_export::delete_expired_batch(conn, now, batch_size)
+        })?,
+    })
+}
+
+/// Permanently removes accounts whose deletion is older than `retention_period` seconds.
+pub fn purge_deleted_accounts(
+    conn: &PgConnection,
+    retention_period: i64,
+    batch_size: i64,
+) -> QueryResult<usize> {
+    let deleted_before =
+        chrono::Utc::now().naive_utc() - chrono::Duration::seconds(retention_period);
+
+    in_batches(batch_size, || {
+        user::purge_deleted_batch(conn, deleted_before, batch_size)
+    })
+}
+
+type Job = fn(&PgConnection, &CleanupConfig) -> QueryResult<String>;
+
+fn token_cleanup_job(conn: &PgConnection, config: &CleanupConfig) -> QueryResult<String> {
+    purge_tokens(conn, config.token_cleanup_batch_size).map(|report| {
+        format!(
+            "removed {} expired and {} revoked refresh tokens, {} expired denylist entries, {} expired account exports",
+            report.expired_refresh_tokens,
+            report.revoked_refresh_tokens,
+            report.expired_revoked_tokens,
+            report.expired_account_exports
+        )
+    })
+}
+
+fn account_purge_job(conn: &PgConnection, config: &CleanupConfig) -> QueryResult<String> {
+    purge_deleted_accounts(
+        conn,
+        config.account_retention_period,
+        config.account_purge_batch_size,
+    )
+    .map(|purged| format!("purged {} deleted accounts", purged))
+}
+
+async fn run_job(conn: &DbConn, config: CleanupConfig, job: Job) -> Result<String, JobError> {
+    conn.run(move |c| job(c, &config))
+        .await
+        .map_err(JobError::Query)
+}
+
+/// Seconds to wait before retrying an email that failed `attempts` times.
+fn retry_delay(config: &OutboxConfig, attempts: i32) -> i64 {
+    config.email_retry_delay * (1 << (attempts - 1).clamp(0, 16))
+}
+
+/// Sends the due emails of the outbox. Delivered emails are deleted, failed ones are
+/// retried with exponential backoff until `email_max_attempts`, then marked dead. Dead
+/// emails are deleted after `email_dead_letter_retention`.
+pub async fn deliver_emails(
+    conn: &DbConn,
+    transport: Arc<dyn EmailTransport>,
+    cipher: OutboxCipher,
+    config: OutboxConfig,
+) -> Result<Option<String>, JobError> {
+    let now = chrono::Utc::now().naive_utc();
+    let leased_until = now + chrono::Duration::seconds(OUTBOX_LEASE_SECONDS);
+    let dead_before = now - chrono::Duration::seconds(config.email_dead_letter_retention);
+    let batch_size = config.email_outbox_batch_size;
+    let (expired, due) = conn
+        .run(move |c| {
+            let expired = email_outbox::delete_dead_batch(c, dead_before, batch_size)?;
+            let due = email_outbox::claim_due(c, now, leased_until, batch_size)?;
+
+            Ok((expired, due))
+        })
+        .await
+        .map_err(JobError::Query)?;
+
+    if expired == 0 && due.is_empty() {
+        return Ok(None);
+    }
+
+    let mut outcomes = vec![];
+    for email in due {
+        let (id, attempts) = (email.id, email.attempts + 1);
+        let result = match Email::open(email, &cipher) {
+            Ok(email) => transport.send(&email).await,
+            Err(e) => Err(e),
+        };
+        outcomes.push((id, attempts, result.map_err(|e| format!("{:?}", e))));
+    }
+
+    conn.run(move |c| {
+        let (mut delivered, mut retried, mut dead) = (0, 0, 0);
+        for (id, attempts, result) in outcomes {
+            match result {
+                Ok(_) => {
+                    email_outbox::delete(c, id)?;
+                    delivered += 1;
+                }
+                Err(e) if attempts < config.email_max_attempts => {
+                    let next_attempt_at = chrono::Utc::now().naive_utc()
+                        + chrono::Duration::seconds(retry_delay(&config, attempts));
+                    email_outbox::record_failure(c, id, attempts, e, Some(next_attempt_at))?;
+                    retried += 1;
+                }
+                Err(e) => {
+                    email_outbox::record_failure(c, id, attempts, e, None)?;
+                    dead += 1;
+                }
+            }
+        }
+
+        Ok(Some(format!(
+            "delivered {} emails, {} to retry, {} dead, {} expired",
+            delivered, retried, dead, expired
+        )))
+    })
+    .await
+    .map_err(JobError::Query)
+}
+
+/// Makes the most recently promoted signing key active, if it is not already.
+pub async fn sync_signing_key(
+    conn: &DbConn,
+    keyring: Arc<KeyRing>,
+) -> Result<Option<String>, JobError> {
+    let kids = keyring.kids();
+    let promoted = conn
+        .run(move |c| signing_key_promotion::find_latest(c, kids))
+        .await
+        .map_err(JobError::Query)?;
+
+    match promoted {
+        Some(kid) if kid != keyring.active_kid() => {
+            keyring.promote(&kid).ok();
+            Ok(Some(format!("activated signing key {}", kid)))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Handle on the `DbConn` pool for jobs outliving the liftoff callback. `DbConn::get_one`
+/// looks the pool up in the managed state of a rocket, so the handle keeps one that manages
+/// the pool and nothing else.
+#[derive(Clone)]
+struct JobPool(Arc<Rocket<Build>>);
+
+impl JobPool {
+    async fn from(rocket: &Rocket<Orbit>) -> Option<Self> {
+        let pool = ConnectionPool::<DbConn, PgConnection>::get_pool(rocket).await?;
+
+        Some(Self(Arc::new(
+            rocket::custom(rocket.config().clone()).manage(pool),
+        )))
+    }
+
+    async fn get(&self) -> Option<DbConn> {
+        DbConn::get_one(&self.0).await
+    }
+}
+
+/// Runs `job` every `interval` seconds until shutdown, logging its summary if it has one.
+/// Every run takes a connection of its own, a tick without one is skipped.
+fn schedule<F, Fut>(
+    rocket: &Rocket<Orbit>,
+    pool: JobPool,
+    name: &'static str,
+    interval: u64,
+    mut job: F,
+) where
+    F: FnMut(DbConn) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<Option<String>, JobError>> + Send,
+{
+    if interval == 0 {
+        info!("{} disabled", name);
+        return;
+    }
+
+    let mut shutdown = rocket.shutdown();
+    tokio::spawn(async move {
+        let period = Duration::from_secs(interval);
+        let mut ticks = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => {},
+                _ = &mut shutdown => break,
+            }
+
+            let conn = match pool.get().await {
+                Some(conn) => conn,
+                None => {
+                    warn!("{} skipped, no database connection", name);
+                    continue;
+                }
+            };
+            match job(conn).await {
+                Ok(Some(summary)) => info!("{} {}", name, summary),
+                Ok(None) => {}
+                Err(e) => error!("{} failed: {:?}", name, e),
+            }
+        }
+    });
+}
+
+fn schedule_cleanup(
+    rocket: &Rocket<Orbit>,
+    pool: JobPool,
+    name: &'static str,
+    interval: u64,
+    job: Job,
+) {
+    let config = rocket.state::<CleanupConfig>().unwrap().clone();
+    schedule(rocket, pool, name, interval, move |conn| {
+        let config = config.clone();
+        async move { run_job(&conn, config, job).await.map(Some) }
+    })
+}
+
+/// Activates the last promoted signing key, then periodically purges expired and revoked
+/// tokens and deleted accounts, delivers queued emails and follows signing key promotions
+/// made on other instances.
+pub fn scheduler() -> AdHoc {
+    AdHoc::on_liftoff("Scheduled jobs", |rocket| {
+        Box::pin(async move {
+            let pool = match JobPool::from(rocket).await {
+                Some(pool) => pool,
+                None => return warn!("scheduled jobs disabled, no database pool"),
+            };
+
+            let cleanup_config = rocket.state::<CleanupConfig>().unwrap();
+            schedule_cleanup(
+                rocket,
+                pool.clone(),
+                "token cleanup",
+                cleanup_config.token_cleanup_interval,
+                token_cleanup_job,
+            );
+            schedule_cleanup(
+                rocket,
+                pool.clone(),
+                "account purge",
+                cleanup_config.account_purge_interval,
+                account_purge_job,
+            );
+
+            // While `email_enabled` is off no email is rendered into the outbox, so there is
+            // nothing to deliver; emails left from before are delivered once it is back on.
+            let mailer = rocket.state::<Mailer>().unwrap();
+            match mailer.is_enabled() {
+                true => {
+                    let (transport, cipher) = (mailer.transport(), mailer.cipher());
+                    let config = rocket.state::<OutboxConfig>().unwrap().clone();
+                    let interval = config.email_outbox_interval;
+                    schedule(
+                        rocket,
+                        pool.clone(),
+                        "email delivery",
+                        interval,
+                        move |conn| {
+                            let (transport, cipher, config) =
+                                (transport.clone(), cipher.clone(), config.clone());
+                            async move { deliver_emails(&conn, transport, cipher, config).await }
+                        },
+                    )
+                }
+                false => info!("email delivery disabled"),
+            }
+
+            let jwt_config = rocket.state::<JWTConfig>().unwrap();
+            let keyring = jwt_config.keyring.clone();
+            match pool.get().await {
+                Some(conn) => match sync_signing_key(&conn, keyring.clone()).await {
+                    Ok(Some(summary)) => info!("signing key sync {}", summary),
+                    Ok(None) => {}
+                    Err(e) => error!("signing key sync failed: {:?}", e),
+                },
+                None => warn!("signing key sync skipped, no database connection"),
+            }
+            schedule(
+                rocket,
+                pool,
+                "signing key sync",
+                jwt_config.key_sync_interval,
+                move |conn| {
+                    let keyring = keyring.clone();
+                    async move { sync_signing_key(&conn, keyring).await }
+                },
+            )
+        })
+    })
+}
@@ -1,44 +1,323 @@
-use jsonwebtoken::{Algorithm, Header, Validation};
-use serde::{Deserialize, Serialize};
+use crate::{models::user::User, util::random::random_id};
+use jsonwebtoken::{
+    decode, decode_header, encode,
+    errors::ErrorKind,
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+        EllipticCurveKeyType, Jwk, JwkSet, OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse,
+        RSAKeyParameters, RSAKeyType,
+    },
+    Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation,
+};
+use ring::signature::{
+    EcdsaKeyPair, Ed25519KeyPair, KeyPair, RsaKeyPair, ECDSA_P256_SHA256_FIXED_SIGNING,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::sync::RwLock;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenUse {
+    Access,
+    Refresh,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     sub: String,
     iss: String,
     pub exp: usize,
-    iat: usize,
+    pub iat: usize,
     nbf: usize,
+    pub jti: String,
+    pub token_use: TokenUse,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
 }
 
 const ISSUER: &str = "beemstream";
 
 impl Claims {
-    pub fn new(identifier: &str, refresh_interval: i64) -> Claims {
+    pub fn new(
+        user: &User,
+        refresh_interval: i64,
+        token_use: TokenUse,
+        session_id: &str,
+    ) -> Claims {
         let time_now = chrono::Utc::now();
         let exp = time_now + chrono::Duration::seconds(refresh_interval);
         let nbf = time_now + chrono::Duration::seconds(2);
 
         Claims {
-            sub: String::from(identifier),
+            sub: user.id.to_string(),
             iss: String::from(ISSUER),
             exp: exp.timestamp() as usize,
             iat: time_now.timestamp() as usize,
             nbf: nbf.timestamp() as usize,
+            jti: random_id(),
+            token_use,
+            sid: Some(session_id.to_owned()),
+            username: Some(user.username.to_owned()),
+            email: Some(user.email.to_owned()),
+            email_verified: Some(user.email_verified_at.is_some()),
         }
     }
 
     pub fn sub(&self) -> &str {
         &self.sub
     }
+
+    pub fn user_id(&self) -> Option<i32> {
+        self.sub.parse().ok()
+    }
+}
+
+const EMAIL_VERIFICATION_PURPOSE: &str = "email_verification";
+
+/// Claims of the link emailed to confirm an address. The token is only valid for the
+/// address it was issued for, so changing the email invalidates older links.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailVerificationClaims {
+    sub: String,
+    iss: String,
+    exp: usize,
+    iat: usize,
+    purpose: String,
+    pub email: String,
+}
+
+impl EmailVerificationClaims {
+    pub fn new(user: &User, expiry: i64) -> Self {
+        let time_now = chrono::Utc::now();
+        let exp = time_now + chrono::Duration::seconds(expiry);
+
+        Self {
+            sub: user.id.to_string(),
+            iss: String::from(ISSUER),
+            exp: exp.timestamp() as usize,
+            iat: time_now.timestamp() as usize,
+            purpose: String::from(EMAIL_VERIFICATION_PURPOSE),
+            email: user.email.to_owned(),
+        }
+    }
+
+    pub fn user_id(&self) -> Option<i32> {
+        match self.purpose == EMAIL_VERIFICATION_PURPOSE {
+            true => self.sub.parse().ok(),
+            false => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum KeyError {
+    Io(std::io::Error),
+    Pem(pem::PemError),
+    Rejected(ring::error::KeyRejected),
+    Jwt(jsonwebtoken::errors::Error),
+    Unsupported(Algorithm),
+    UnknownKid(String),
+    DuplicateKid(String),
+}
+
+/// Key used for tokens that were issued before they carried a `kid` header.
+pub const LEGACY_KID: &str = "default";
+
+pub struct SigningKey {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    validation: Validation,
+    jwk: Option<Jwk>,
+}
+
+impl SigningKey {
+    pub fn from_secret(kid: &str, secret: &str) -> Self {
+        Self {
+            kid: kid.to_owned(),
+            algorithm: Algorithm::HS512,
+            encoding_key: EncodingKey::from_secret(secret.as_ref()),
+            decoding_key: DecodingKey::from_secret(secret.as_ref()),
+            validation: jwt_validation(Algorithm::HS512),
+            jwk: None,
+        }
+    }
+
+    pub fn from_pem_file(kid: &str, algorithm: Algorithm, path: &str) -> Result<Self, KeyError> {
+        let private_pem = std::fs::read(path).map_err(KeyError::Io)?;
+        Self::from_pem(kid, algorithm, &private_pem)
+    }
+
+    /// Loads a private key and derives the public half that is published in the JWKS.
+    pub fn from_pem(kid: &str, algorithm: Algorithm, private_pem: &[u8]) -> Result<Self, KeyError> {
+        let der = pem::parse(private_pem).map_err(KeyError::Pem)?.contents;
+
+        let (decoding_key, parameters) = match algorithm {
+            Algorithm::RS256 => {
+                let key_pair = RsaKeyPair::from_pkcs8(&der)
+                    .or_else(|_| RsaKeyPair::from_der(&der))
+                    .map_err(KeyError::Rejected)?;
+                let public_key = key_pair.public_key();
+                let modulus = public_key.modulus();
+                let exponent = public_key.exponent();
+
+                (
+                    DecodingKey::from_rsa_raw_components(
+                        modulus.big_endian_without_leading_zero(),
+                        exponent.big_endian_without_leading_zero(),
+                    ),
+                    AlgorithmParameters::RSA(RSAKeyParameters {
+                        key_type: RSAKeyType::RSA,
+                        n: base64_url(modulus.big_endian_without_leading_zero()),
+                        e: base64_url(exponent.big_endian_without_leading_zero()),
+                    }),
+                )
+            }
+            Algorithm::ES256 => {
+                let key_pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &der)
+                    .map_err(KeyError::Rejected)?;
+                // Uncompressed SEC1 point: 0x04 || x || y
+                let point = key_pair.public_key().as_ref();
+
+                (
+                    DecodingKey::from_ec_der(point),
+                    AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                        key_type: EllipticCurveKeyType::EC,
+                        curve: EllipticCurve::P256,
+                        x: base64_url(&point[1..33]),
+                        y: base64_url(&point[33..65]),
+                    }),
+                )
+            }
+            Algorithm::EdDSA => {
+                let key_pair =
+                    Ed25519KeyPair::from_pkcs8_maybe_unchecked(&der).map_err(KeyError::Rejected)?;
+                let public_key = key_pair.public_key().as_ref();
+
+                (
+                    DecodingKey::from_ed_der(public_key),
+                    AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                        key_type: OctetKeyPairType::OctetKeyPair,
+                        curve: EllipticCurve::Ed25519,
+                        x: base64_url(public_key),
+                    }),
+                )
+            }
+            _ => return Err(KeyError::Unsupported(algorithm)),
+        };
+
+        let encoding_key = match algorithm {
+            Algorithm::RS256 => EncodingKey::from_rsa_pem(private_pem),
+            Algorithm::ES256 => EncodingKey::from_ec_pem(private_pem),
+            _ => EncodingKey::from_ed_pem(private_pem),
+        }
+        .map_err(KeyError::Jwt)?;
+
+        Ok(Self {
+            kid: kid.to_owned(),
+            algorithm,
+            encoding_key,
+            decoding_key,
+            validation: jwt_validation(algorithm),
+            jwk: Some(Jwk {
+                common: CommonParameters {
+                    public_key_use: Some(PublicKeyUse::Signature),
+                    algorithm: Some(algorithm),
+                    key_id: Some(kid.to_owned()),
+                    ..CommonParameters::default()
+                },
+                algorithm: parameters,
+            }),
+        })
+    }
+}
+
+/// The active key signs new tokens; every key in the ring is still accepted when validating.
+pub struct KeyRing {
+    keys: Vec<SigningKey>,
+    active_kid: RwLock<String>,
+}
+
+impl KeyRing {
+    pub fn new(keys: Vec<SigningKey>, active_kid: &str) -> Result<Self, KeyError> {
+        if !keys.iter().any(|k| k.kid == active_kid) {
+            return Err(KeyError::UnknownKid(active_kid.to_owned()));
+        }
+
+        Ok(Self {
+            keys,
+            active_kid: RwLock::new(active_kid.to_owned()),
+        })
+    }
+
+    pub fn active_kid(&self) -> String {
+        self.active_kid.read().unwrap().clone()
+    }
+
+    pub fn kids(&self) -> Vec<String> {
+        self.keys.iter().map(|k| k.kid.to_owned()).collect()
+    }
+
+    fn find(&self, kid: &str) -> Option<&SigningKey> {
+        self.keys.iter().find(|k| k.kid == kid)
+    }
+
+    pub fn promote(&self, kid: &str) -> Result<(), KeyError> {
+        match self.find(kid) {
+            Some(key) => {
+                *self.active_kid.write().unwrap() = key.kid.to_owned();
+                Ok(())
+            }
+            None => Err(KeyError::UnknownKid(kid.to_owned())),
+        }
+    }
+
+    pub fn encode<T: Serialize>(&self, claims: &T) -> Result<String, jsonwebtoken::errors::Error> {
+        let active_kid = self.active_kid();
+        let key = self.find(&active_kid).unwrap();
+        let mut header = generate_header(key.algorithm);
+        header.kid = Some(active_kid);
+
+        encode(&header, claims, &key.encoding_key)
+    }
+
+    pub fn decode<T: DeserializeOwned>(
+        &self,
+        token: &str,
+    ) -> Result<TokenData<T>, jsonwebtoken::errors::Error> {
+        let header = decode_header(token)?;
+        let kid = header.kid.as_deref().unwrap_or(LEGACY_KID);
+        let key = self.find(kid).ok_or(ErrorKind::InvalidToken)?;
+
+        decode::<T>(token, &key.decoding_key, &key.validation)
+    }
+
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self.keys.iter().filter_map(|k| k.jwk.clone()).collect(),
+        }
+    }
+}
+
+fn base64_url(bytes: &[u8]) -> String {
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
 }
 
-pub fn generate_header() -> Header {
-    Header::new(Algorithm::HS512)
+fn generate_header(algorithm: Algorithm) -> Header {
+    Header::new(algorithm)
 }
 
-pub fn jwt_validation() -> Validation {
-    let mut validation = Validation::new(Algorithm::HS512);
+fn jwt_validation(algorithm: Algorithm) -> Validation {
+    let mut validation = Validation::new(algorithm);
     validation.leeway = 2;
-    validation.iss = Some(ISSUER.to_owned());
+    validation.set_issuer(&[ISSUER]);
     validation
 }
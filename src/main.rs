@@ -3,6 +3,9 @@ extern crate diesel;
 
 mod database;
 mod email_sender;
+mod email_templates;
+mod i18n;
+mod jobs;
 mod jwt;
 mod models;
 mod oauth;
@@ -15,28 +18,62 @@ mod util;
 mod test;
 
 use database::DbConn;
-use jwt::jwt_validation;
-use rocket::{
-    catch, catchers,
-    launch, routes, Build, Request, Rocket, Route,
+use email_sender::Mailer;
+use i18n::Catalogs;
+use rocket::{catch, catchers, launch, routes, Build, Request, Rocket, Route};
+use util::{
+    globals::{
+        CleanupConfig, EmailConfig, GlobalConfig, JWTConfig, OutboxConfig, SigningConfig,
+        TwitchConfig,
+    },
+    outbox_cipher::OutboxCipher,
 };
-use util::globals::{EmailConfig, GlobalConfig, JWTConfig, TwitchConfig};
 
 #[catch(401)]
 fn not_authorized(_req: &Request) {}
 
 #[launch]
 fn get_rocket() -> Rocket<Build> {
-    let rocket = rocket::build();
+    build_rocket(rocket::build())
+}
+
+fn build_rocket(rocket: Rocket<Build>) -> Rocket<Build> {
     let routes: Vec<Route> = routes![
         routes::register::register_user,
+        routes::email_verification::verify_email,
+        routes::email_verification::resend_verification_email,
+        routes::email_change::request_email_change,
+        routes::email_change::confirm_email_change,
+        routes::email_change::cancel_email_change,
         routes::login::login,
+        routes::logout::logout,
+        routes::password::forgot_password,
+        routes::password::reset_password,
+        routes::password::change_password,
         routes::refresh_token::refresh_token,
         routes::users::authenticate,
         routes::oauth::twitch_auth,
         routes::oauth::twitch_token,
         routes::oauth::logout_twitch,
         routes::profile_lookup::profile_lookup,
+        routes::account::delete_account,
+        routes::account::update_locale,
+        routes::account_export::request_account_export,
+        routes::account_export::download_account_export,
+        routes::username::update_username,
+        routes::username::lookup_username,
+        routes::jwks::jwks,
+        routes::signing_keys::signing_keys,
+        routes::signing_keys::promote_signing_key,
+        routes::email_templates::preview_email_template,
+        routes::email_outbox::email_outbox,
+        routes::email_outbox::retry_email,
+        routes::introspect::introspect,
+        routes::tombstones::tombstones,
+        routes::revoke::revoke,
+        routes::sessions::sessions,
+        routes::sessions::revoke_session,
+        routes::sessions::revoke_other_sessions,
     ];
 
     let figment = rocket.figment();
@@ -44,16 +81,30 @@ fn get_rocket() -> Rocket<Build> {
     let global_config: GlobalConfig = figment.extract().expect("global config");
     let twitch_config: TwitchConfig = figment.extract().expect("twitch config");
     let email_config: EmailConfig = figment.extract().expect("email config");
-    let jwt = JWTConfig {
-        validation: jwt_validation(),
-    };
+    let catalogs = Catalogs::load().expect("message catalogs");
+    let mailer = Mailer::from(
+        &email_config,
+        catalogs.clone(),
+        OutboxCipher::new(global_config.token_hash_key()),
+    )
+    .expect("email transport");
+    let outbox_config: OutboxConfig = figment.extract().expect("outbox config");
+    let signing_config: SigningConfig = figment.extract().expect("signing config");
+    let cleanup_config: CleanupConfig = figment.extract().expect("cleanup config");
+    let jwt =
+        JWTConfig::from(&signing_config, &global_config.auth_secret_key).expect("jwt signing key");
 
     rocket
         .mount("/auth", routes)
         .attach(DbConn::fairing())
+        .attach(jobs::scheduler())
         .manage(global_config)
         .manage(twitch_config)
         .manage(email_config)
+        .manage(mailer)
+        .manage(catalogs)
+        .manage(outbox_config)
         .manage(jwt)
+        .manage(cleanup_config)
         .register("/", catchers![not_authorized])
 }
@@ -0,0 +1,24 @@
+use crate::{models::user::User, schema::account_exports};
+use serde::{Deserialize, Serialize};
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Associations, Debug, PartialEq)]
+#[belongs_to(User, foreign_key = "user_id")]
+#[table_name = "account_exports"]
+pub struct AccountExport {
+    pub id: i32,
+    pub user_id: i32,
+    pub token_hash: String,
+    pub archive: Option<String>,
+    pub expiry: chrono::NaiveDateTime,
+    pub created_at: chrono::NaiveDateTime,
+    pub completed_at: Option<chrono::NaiveDateTime>,
+    pub failed_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Insertable, Serialize, Deserialize)]
+#[table_name = "account_exports"]
+pub struct NewAccountExport {
+    pub user_id: i32,
+    pub token_hash: String,
+    pub expiry: chrono::NaiveDateTime,
+}
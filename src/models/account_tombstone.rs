@@ -0,0 +1,18 @@
+use crate::schema::account_tombstones;
+use serde::{Deserialize, Serialize};
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, PartialEq)]
+#[table_name = "account_tombstones"]
+pub struct AccountTombstone {
+    pub id: i32,
+    pub user_id: i32,
+    pub deleted_at: Option<chrono::NaiveDateTime>,
+    pub purged_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, Serialize, Deserialize)]
+#[table_name = "account_tombstones"]
+pub struct NewAccountTombstone {
+    pub user_id: i32,
+    pub deleted_at: Option<chrono::NaiveDateTime>,
+}
@@ -0,0 +1,24 @@
+use crate::schema::email_change_requests;
+use serde::{Deserialize, Serialize};
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, PartialEq)]
+#[table_name = "email_change_requests"]
+pub struct EmailChangeRequest {
+    pub id: i32,
+    pub user_id: i32,
+    pub new_email: String,
+    pub confirm_token_hash: String,
+    pub cancel_token_hash: String,
+    pub expiry: chrono::NaiveDateTime,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, Serialize, Deserialize)]
+#[table_name = "email_change_requests"]
+pub struct NewEmailChangeRequest {
+    pub user_id: i32,
+    pub new_email: String,
+    pub confirm_token_hash: String,
+    pub cancel_token_hash: String,
+    pub expiry: chrono::NaiveDateTime,
+}
@@ -0,0 +1,55 @@
+use crate::schema::email_outbox;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutboxStatus {
+    Pending,
+    /// Gave up after `email_max_attempts`; kept for `email_dead_letter_retention` so an admin
+    /// can retry it.
+    Dead,
+}
+
+impl OutboxStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutboxStatus::Pending => "pending",
+            OutboxStatus::Dead => "dead",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "pending" => Some(OutboxStatus::Pending),
+            "dead" => Some(OutboxStatus::Dead),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, PartialEq)]
+#[table_name = "email_outbox"]
+pub struct OutboxEmail {
+    pub id: i32,
+    pub user_id: i32,
+    pub recipient: String,
+    pub subject: String,
+    /// Sealed with the `OutboxCipher`, like `html`.
+    pub body: String,
+    pub html: Option<String>,
+    pub status: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub next_attempt_at: chrono::NaiveDateTime,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Debug)]
+#[table_name = "email_outbox"]
+pub struct NewOutboxEmail {
+    pub user_id: i32,
+    pub recipient: String,
+    pub subject: String,
+    pub body: String,
+    pub html: Option<String>,
+}
@@ -0,0 +1,21 @@
+use crate::schema::password_reset_tokens;
+use serde::{Deserialize, Serialize};
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, PartialEq)]
+#[table_name = "password_reset_tokens"]
+pub struct PasswordResetToken {
+    pub id: i32,
+    pub token_hash: String,
+    pub user_id: i32,
+    pub expiry: chrono::NaiveDateTime,
+    pub used_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, Serialize, Deserialize)]
+#[table_name = "password_reset_tokens"]
+pub struct NewPasswordResetToken {
+    pub token_hash: String,
+    pub user_id: i32,
+    pub expiry: chrono::NaiveDateTime,
+}
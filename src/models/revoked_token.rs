@@ -0,0 +1,17 @@
+use crate::schema::revoked_tokens;
+use serde::{Deserialize, Serialize};
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, PartialEq)]
+#[table_name = "revoked_tokens"]
+pub struct RevokedToken {
+    pub id: i32,
+    pub jti: String,
+    pub expiry: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, Serialize, Deserialize)]
+#[table_name = "revoked_tokens"]
+pub struct NewRevokedToken {
+    pub jti: String,
+    pub expiry: chrono::NaiveDateTime,
+}
@@ -0,0 +1,51 @@
+use crate::{models::user::User, schema::security_events};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SecurityEventType {
+    RefreshTokenReuse,
+    AccountDeleted,
+    AccountRestored,
+    IdentityLinked,
+}
+
+impl SecurityEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SecurityEventType::RefreshTokenReuse => "refresh_token_reuse",
+            SecurityEventType::AccountDeleted => "account_deleted",
+            SecurityEventType::AccountRestored => "account_restored",
+            SecurityEventType::IdentityLinked => "identity_linked",
+        }
+    }
+}
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Associations, Debug, PartialEq)]
+#[belongs_to(User, foreign_key = "user_id")]
+#[table_name = "security_events"]
+pub struct SecurityEvent {
+    pub id: i32,
+    pub user_id: i32,
+    pub event_type: String,
+    pub detail: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, Serialize, Deserialize)]
+#[table_name = "security_events"]
+pub struct NewSecurityEvent {
+    pub user_id: i32,
+    pub event_type: String,
+    pub detail: Option<String>,
+}
+
+impl NewSecurityEvent {
+    pub fn new(user_id: i32, event_type: SecurityEventType, detail: Option<String>) -> Self {
+        Self {
+            user_id,
+            event_type: event_type.as_str().to_owned(),
+            detail,
+        }
+    }
+}
@@ -5,12 +5,8 @@ use crate::{
 use argon2::{self, hash_encoded, verify_encoded_ext, Config};
 use rand::Rng;
 use serde::{Deserialize, Serialize};
-use validator::Validate;
-
-pub enum UserType<'a> {
-    LoginUser(&'a LoginUser),
-    StoredUser(&'a User),
-}
+use std::borrow::Cow;
+use validator::{Validate, ValidationError};
 
 #[derive(Identifiable, Queryable, AsChangeset, Serialize, Deserialize, Debug, PartialEq, Clone)]
 #[table_name = "users"]
@@ -22,6 +18,10 @@ pub struct User {
     pub is_deleted: bool,
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
+    pub email_verified_at: Option<chrono::NaiveDateTime>,
+    pub deleted_at: Option<chrono::NaiveDateTime>,
+    /// Preferred locale for emails, `None` to follow the `Accept-Language` of each request.
+    pub locale: Option<String>,
 }
 
 impl User {
@@ -40,11 +40,11 @@ impl User {
 pub struct NewUserRequest {
     #[validate(required, email(message = "email_invalid"))]
     pub email: Option<String>,
-    #[validate(required, length(min = 4, message = "username_length_invalid"))]
+    #[validate(required, custom = "validate_username")]
     pub username: Option<String>,
     #[validate(
         required,
-        length(min = 12, message = "password_length_invalid"),
+        custom = "validate_password",
         must_match(other = "password_repeat", message = "password_not_matching")
     )]
     pub password: Option<String>,
@@ -52,6 +52,34 @@ pub struct NewUserRequest {
     pub password_repeat: Option<String>,
 }
 
+fn invalid(code: &'static str) -> ValidationError {
+    ValidationError {
+        message: Some(Cow::from(code)),
+        ..ValidationError::new(code)
+    }
+}
+
+/// Usernames have at least 4 characters.
+pub fn validate_username(username: &str) -> Result<(), ValidationError> {
+    match username.chars().count() >= 4 {
+        true => Ok(()),
+        false => Err(invalid("username_length_invalid")),
+    }
+}
+
+/// Passwords have at least 12 characters.
+pub fn validate_password(password: &str) -> Result<(), ValidationError> {
+    match password.chars().count() >= 12 {
+        true => Ok(()),
+        false => Err(invalid("password_length_invalid")),
+    }
+}
+
+/// Email addresses are stored trimmed and lowercased so they compare case-insensitively.
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
 #[derive(Insertable, Deserialize, Serialize)]
 #[table_name = "users"]
 pub struct NewUser {
@@ -61,7 +89,7 @@ pub struct NewUser {
 }
 
 impl NewUser {
-    fn hash_password(password: String, secret_key: &str) -> String {
+    pub fn hash_password(password: String, secret_key: &str) -> String {
         let config = Config {
             secret: secret_key.as_bytes(),
             ..Config::default()
@@ -75,7 +103,7 @@ impl NewUser {
     pub fn from(new_user_request: NewUserRequest, secret_key: &str) -> Self {
         Self {
             username: new_user_request.username.to_owned().unwrap(),
-            email: new_user_request.email.to_owned().unwrap(),
+            email: normalize_email(&new_user_request.email.unwrap()),
             password: NewUser::hash_password(new_user_request.password.unwrap(), secret_key),
         }
     }
@@ -88,24 +116,98 @@ impl Validator for NewUserRequest {}
 #[table_name = "refresh_tokens"]
 pub struct RefreshToken {
     pub id: i32,
-    pub token: String,
+    pub token_hash: String,
     pub expiry: chrono::NaiveDateTime,
     pub user_id: i32,
+    pub family_id: String,
+    pub parent_id: Option<i32>,
+    pub rotated_at: Option<chrono::NaiveDateTime>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub label: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub last_used_at: chrono::NaiveDateTime,
 }
 
 #[derive(Insertable, Serialize, Deserialize)]
 #[table_name = "refresh_tokens"]
 pub struct NewRefreshToken {
-    pub token: String,
+    pub token_hash: String,
     pub expiry: chrono::NaiveDateTime,
     pub user_id: i32,
+    pub family_id: String,
+    pub parent_id: Option<i32>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub label: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub last_used_at: chrono::NaiveDateTime,
+}
+#[derive(Debug, Deserialize, Validate)]
+pub struct ChangePasswordRequest {
+    #[validate(required)]
+    pub current_password: Option<String>,
+    #[validate(
+        required,
+        custom = "validate_password",
+        must_match(other = "password_repeat", message = "password_not_matching")
+    )]
+    pub password: Option<String>,
+    #[validate(required)]
+    pub password_repeat: Option<String>,
 }
+
+impl Validator for ChangePasswordRequest {}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ResetPasswordRequest {
+    #[validate(required)]
+    pub token: Option<String>,
+    #[validate(
+        required,
+        custom = "validate_password",
+        must_match(other = "password_repeat", message = "password_not_matching")
+    )]
+    pub password: Option<String>,
+    #[validate(required)]
+    pub password_repeat: Option<String>,
+}
+
+impl Validator for ResetPasswordRequest {}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct DeleteAccountRequest {
+    #[validate(required)]
+    pub password: Option<String>,
+}
+
+impl Validator for DeleteAccountRequest {}
+
+/// A locale such as `de`, or `null` to follow `Accept-Language` again.
+#[derive(Debug, Deserialize)]
+pub struct LocaleRequest {
+    pub locale: Option<String>,
+}
+
+/// Body of the endpoints that only take an email address. They answer 202 whether or
+/// not the address is registered, so they cannot be used to probe for accounts.
+#[derive(Debug, Deserialize, Validate)]
+pub struct EmailRequest {
+    #[validate(required, email(message = "email_invalid"))]
+    pub email: Option<String>,
+}
+
+impl Validator for EmailRequest {}
+
 #[derive(Debug, Deserialize, Serialize, Validate)]
 pub struct LoginUser {
     #[validate(required)]
     pub identifier: Option<String>,
     #[validate(required, length(min = 12))]
     pub password: Option<String>,
+    /// Clients without cookies ask for the refresh token in the body to refresh with it.
+    #[serde(default)]
+    pub refresh_token_in_body: bool,
 }
 
 impl Validator for LoginUser {}
@@ -0,0 +1,24 @@
+use crate::{models::user::User, schema::user_identities};
+use serde::{Deserialize, Serialize};
+
+pub const TWITCH_PROVIDER: &str = "twitch";
+
+/// An account at an external provider that signs in as a local user.
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Associations, Debug, PartialEq)]
+#[belongs_to(User, foreign_key = "user_id")]
+#[table_name = "user_identities"]
+pub struct UserIdentity {
+    pub id: i32,
+    pub user_id: i32,
+    pub provider: String,
+    pub provider_user_id: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, Serialize, Deserialize)]
+#[table_name = "user_identities"]
+pub struct NewUserIdentity {
+    pub user_id: i32,
+    pub provider: String,
+    pub provider_user_id: String,
+}
@@ -0,0 +1,31 @@
+use crate::{
+    models::user::validate_username, schema::username_history, util::validator::Validator,
+};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, PartialEq)]
+#[table_name = "username_history"]
+pub struct UsernameHistory {
+    pub id: i32,
+    pub user_id: i32,
+    pub username: String,
+    pub changed_at: chrono::NaiveDateTime,
+    pub held_until: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, Serialize, Deserialize)]
+#[table_name = "username_history"]
+pub struct NewUsernameHistory {
+    pub user_id: i32,
+    pub username: String,
+    pub held_until: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ChangeUsernameRequest {
+    #[validate(required, custom = "validate_username")]
+    pub username: Option<String>,
+}
+
+impl Validator for ChangeUsernameRequest {}
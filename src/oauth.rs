@@ -3,20 +3,26 @@ use oauth2::{
     basic::{BasicErrorResponse, BasicTokenType},
     reqwest::HttpClientError,
 };
+use oauth2::{
+    http::{
+        header::{HeaderValue, AUTHORIZATION},
+        HeaderMap, Method, StatusCode,
+    },
+    HttpRequest,
+};
 use oauth2::{
     AccessToken, AuthType, AuthUrl, AuthorizationCode, Client, ClientId, ClientSecret, CsrfToken,
     ExtraTokenFields, RedirectUrl, RefreshToken, RequestTokenError, Scope, TokenResponse,
     TokenType, TokenUrl,
 };
+use rocket::serde::json::serde_json;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
-pub fn twitch_authenticate(
-    client_id: String,
-    client_secret: String,
-    callback_url: String,
-) -> (oauth2::url::Url, CsrfToken) {
-    let client = twitch_client(client_id, client_secret, callback_url);
+use crate::util::globals::TwitchConfig;
+
+pub fn twitch_authenticate(config: &TwitchConfig) -> (oauth2::url::Url, CsrfToken) {
+    let client = twitch_client(config);
     client
         .authorize_url(CsrfToken::new_random)
         .add_scope(Scope::new(
@@ -27,12 +33,9 @@ pub fn twitch_authenticate(
 
 pub fn twitch_exchange_code(
     auth_code: String,
-    client_id: String,
-    client_secret: String,
-    callback_url: String,
+    config: &TwitchConfig,
 ) -> Result<ExchangeSuccess, ExchangeError> {
-    let client =
-        twitch_client(client_id, client_secret, callback_url).set_auth_type(AuthType::RequestBody);
+    let client = twitch_client(config).set_auth_type(AuthType::RequestBody);
 
     client
         .exchange_code(AuthorizationCode::new(auth_code))
@@ -41,33 +44,83 @@ pub fn twitch_exchange_code(
 
 pub fn twitch_refresh_access_token(
     refresh_token: String,
-    client_id: String,
-    client_secret: String,
-    callback_url: String,
+    config: &TwitchConfig,
 ) -> Result<
     TwitchTokenResponse<TwitchFields, BasicTokenType>,
     RequestTokenError<HttpClientError, BasicErrorResponse>,
 > {
-    let client =
-        twitch_client(client_id, client_secret, callback_url).set_auth_type(AuthType::RequestBody);
+    let client = twitch_client(config).set_auth_type(AuthType::RequestBody);
 
     client
         .exchange_refresh_token(&RefreshToken::new(refresh_token))
         .request(http_client)
 }
 
-pub fn twitch_client(
-    client_id: String,
-    client_secret: String,
-    callback_url: String,
-) -> TwitchOauthClient {
+pub fn twitch_client(config: &TwitchConfig) -> TwitchOauthClient {
     TwitchOauthClient::new(
-        ClientId::new(client_id),
-        Some(ClientSecret::new(client_secret)),
-        AuthUrl::new("https://id.twitch.tv/oauth2/authorize".to_string()).unwrap(),
-        Some(TokenUrl::new("https://id.twitch.tv/oauth2/token".to_string()).unwrap()),
+        ClientId::new(config.twitch_client_id.to_owned()),
+        Some(ClientSecret::new(config.twitch_client_secret.to_owned())),
+        AuthUrl::new(format!("{}/authorize", config.twitch_id_url)).unwrap(),
+        Some(TokenUrl::new(format!("{}/token", config.twitch_id_url)).unwrap()),
     )
-    .set_redirect_url(RedirectUrl::new(callback_url).unwrap())
+    .set_redirect_url(RedirectUrl::new(config.twitch_callback_url.to_owned()).unwrap())
+}
+
+/// The signed in Twitch account, as returned by the Helix `users` endpoint.
+#[derive(Clone, Debug, Deserialize)]
+pub struct TwitchUser {
+    pub id: String,
+    pub login: String,
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HelixUsers {
+    data: Vec<TwitchUser>,
+}
+
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum TwitchApiError {
+    Http(HttpClientError),
+    Status(StatusCode),
+    Body(serde_json::Error),
+    NotFound,
+}
+
+/// Looks up the user `access_token` belongs to.
+pub fn twitch_user(
+    access_token: &str,
+    config: &TwitchConfig,
+) -> Result<TwitchUser, TwitchApiError> {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {}", access_token)).unwrap(),
+    );
+    headers.insert(
+        "Client-Id",
+        HeaderValue::from_str(&config.twitch_client_id).unwrap(),
+    );
+
+    let response = http_client(HttpRequest {
+        url: oauth2::url::Url::parse(&format!("{}/users", config.twitch_api_url)).unwrap(),
+        method: Method::GET,
+        headers,
+        body: vec![],
+    })
+    .map_err(TwitchApiError::Http)?;
+
+    if response.status_code != StatusCode::OK {
+        return Err(TwitchApiError::Status(response.status_code));
+    }
+
+    serde_json::from_slice::<HelixUsers>(&response.body)
+        .map_err(TwitchApiError::Body)?
+        .data
+        .into_iter()
+        .next()
+        .ok_or(TwitchApiError::NotFound)
 }
 
 pub type ExchangeSuccess = TwitchTokenResponse<TwitchFields, BasicTokenType>;
@@ -0,0 +1,88 @@
+use crate::models::account_export::{AccountExport, NewAccountExport};
+use crate::schema::account_exports;
+use crate::{database::DbConn, routes::users_util::get_auth_error_response};
+use rocket_sync_db_pools::diesel::{self, prelude::*};
+
+/// A user has at most one export; a new request replaces the previous one.
+pub async fn replace_for_user(
+    conn: &DbConn,
+    export: NewAccountExport,
+) -> Result<AccountExport, crate::util::response::Error> {
+    conn.run(|c| {
+        c.transaction(|| {
+            let previous =
+                account_exports::table.filter(account_exports::user_id.eq(export.user_id));
+            diesel::delete(previous).execute(c)?;
+
+            diesel::insert_into(account_exports::table)
+                .values(export)
+                .get_result::<AccountExport>(c)
+        })
+        .map_err(get_auth_error_response)
+    })
+    .await
+}
+
+pub async fn complete(
+    conn: &DbConn,
+    id: i32,
+    archive: String,
+) -> Result<usize, crate::util::response::Error> {
+    conn.run(move |c| {
+        diesel::update(account_exports::table.find(id))
+            .set((
+                account_exports::archive.eq(archive),
+                account_exports::completed_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .execute(c)
+            .map_err(get_auth_error_response)
+    })
+    .await
+}
+
+pub async fn fail(conn: &DbConn, id: i32) -> Result<usize, crate::util::response::Error> {
+    conn.run(move |c| {
+        diesel::update(account_exports::table.find(id))
+            .set(account_exports::failed_at.eq(chrono::Utc::now().naive_utc()))
+            .execute(c)
+            .map_err(get_auth_error_response)
+    })
+    .await
+}
+
+pub async fn find_by_token(
+    conn: &DbConn,
+    token_hash: String,
+) -> Result<Option<AccountExport>, crate::util::response::Error> {
+    conn.run(move |c| {
+        account_exports::table
+            .filter(account_exports::token_hash.eq(token_hash))
+            .filter(account_exports::expiry.gt(chrono::Utc::now().naive_utc()))
+            .first::<AccountExport>(c)
+            .optional()
+            .map_err(get_auth_error_response)
+    })
+    .await
+}
+
+pub async fn delete(conn: &DbConn, id: i32) -> Result<usize, crate::util::response::Error> {
+    conn.run(move |c| {
+        diesel::delete(account_exports::table.find(id))
+            .execute(c)
+            .map_err(get_auth_error_response)
+    })
+    .await
+}
+
+pub fn delete_expired_batch(
+    c: &PgConnection,
+    now: chrono::NaiveDateTime,
+    batch_size: i64,
+) -> QueryResult<usize> {
+    let expired = account_exports::table
+        .select(account_exports::id)
+        .filter(account_exports::expiry.lt(now))
+        .limit(batch_size)
+        .load::<i32>(c)?;
+    diesel::delete(account_exports::table.filter(account_exports::id.eq_any(expired))).execute(c)
+}
@@ -0,0 +1,21 @@
+use crate::models::account_tombstone::AccountTombstone;
+use crate::schema::account_tombstones;
+use crate::{database::DbConn, routes::users_util::get_auth_error_response};
+use rocket_sync_db_pools::diesel::prelude::*;
+
+/// Tombstones with an id greater than `after`, oldest first.
+pub async fn find_after(
+    conn: &DbConn,
+    after: i32,
+    limit: i64,
+) -> Result<Vec<AccountTombstone>, crate::util::response::Error> {
+    conn.run(move |c| {
+        account_tombstones::table
+            .filter(account_tombstones::id.gt(after))
+            .order(account_tombstones::id.asc())
+            .limit(limit)
+            .load::<AccountTombstone>(c)
+            .map_err(get_auth_error_response)
+    })
+    .await
+}
@@ -0,0 +1,94 @@
+use crate::models::{
+    email_change_request::{EmailChangeRequest, NewEmailChangeRequest},
+    email_outbox::NewOutboxEmail,
+};
+use crate::repository::{email_outbox, user};
+use crate::schema::email_change_requests;
+use crate::{database::DbConn, routes::users_util::get_auth_error_response};
+use rocket_sync_db_pools::diesel::{self, prelude::*};
+
+/// A user has at most one pending change; a new request replaces the previous one.
+/// `emails` are queued with it.
+pub async fn replace_for_user(
+    conn: &DbConn,
+    change_request: NewEmailChangeRequest,
+    emails: Vec<NewOutboxEmail>,
+) -> Result<usize, crate::util::response::Error> {
+    conn.run(|c| {
+        c.transaction(|| {
+            let previous = email_change_requests::table
+                .filter(email_change_requests::user_id.eq(change_request.user_id));
+            diesel::delete(previous).execute(c)?;
+
+            let inserted = diesel::insert_into(email_change_requests::table)
+                .values(change_request)
+                .execute(c)?;
+            email_outbox::queue(c, emails)?;
+
+            Ok(inserted)
+        })
+        .map_err(get_auth_error_response)
+    })
+    .await
+}
+
+/// Moves the user of an unexpired `token_hash` to the new address and removes the request
+/// in one transaction, so the token works once. Returns the user id, or `None` when there
+/// is no such request or its user is deleted.
+pub async fn confirm(
+    conn: &DbConn,
+    token_hash: String,
+) -> Result<Option<i32>, crate::util::response::Error> {
+    conn.run(move |c| {
+        c.transaction(|| {
+            let change_request = email_change_requests::table
+                .filter(email_change_requests::confirm_token_hash.eq(token_hash))
+                .filter(email_change_requests::expiry.gt(chrono::Utc::now().naive_utc()))
+                .for_update()
+                .first::<EmailChangeRequest>(c)
+                .optional()?;
+            let change_request = match change_request {
+                Some(change_request) => change_request,
+                None => return Ok(None),
+            };
+
+            // The address may have been taken since the change was requested.
+            if user::set_email(c, change_request.user_id, &change_request.new_email)? == 0 {
+                return Ok(None);
+            }
+            diesel::delete(email_change_requests::table.find(change_request.id)).execute(c)?;
+
+            Ok(Some(change_request.user_id))
+        })
+    })
+    .await
+}
+
+pub async fn delete_by_cancel_token(
+    conn: &DbConn,
+    token_hash: String,
+) -> Result<usize, crate::util::response::Error> {
+    conn.run(move |c| {
+        let change_request = email_change_requests::table
+            .filter(email_change_requests::cancel_token_hash.eq(token_hash));
+        diesel::delete(change_request)
+            .execute(c)
+            .map_err(get_auth_error_response)
+    })
+    .await
+}
+
+pub async fn find_for_user(
+    conn: &DbConn,
+    user_id: i32,
+) -> Result<Option<EmailChangeRequest>, crate::util::response::Error> {
+    conn.run(move |c| {
+        email_change_requests::table
+            .filter(email_change_requests::user_id.eq(user_id))
+            .filter(email_change_requests::expiry.gt(chrono::Utc::now().naive_utc()))
+            .first::<EmailChangeRequest>(c)
+            .optional()
+            .map_err(get_auth_error_response)
+    })
+    .await
+}
@@ -0,0 +1,127 @@
+use crate::models::email_outbox::{NewOutboxEmail, OutboxEmail, OutboxStatus};
+use crate::schema::email_outbox;
+use crate::{database::DbConn, routes::users_util::get_auth_error_response};
+use rocket_sync_db_pools::diesel::{self, prelude::*};
+
+/// Queues `emails` on `c`, so callers can queue them in the transaction of the change
+/// that triggers them.
+pub fn queue(c: &PgConnection, emails: Vec<NewOutboxEmail>) -> QueryResult<usize> {
+    match emails.is_empty() {
+        true => Ok(0),
+        false => diesel::insert_into(email_outbox::table)
+            .values(emails)
+            .execute(c),
+    }
+}
+
+pub async fn insert(
+    conn: &DbConn,
+    emails: Vec<NewOutboxEmail>,
+) -> Result<usize, crate::util::response::Error> {
+    conn.run(|c| queue(c, emails).map_err(get_auth_error_response))
+        .await
+}
+
+/// Leases up to `batch_size` due emails until `leased_until`, so concurrent workers skip
+/// them and a crashed worker's emails are picked up again afterwards.
+pub fn claim_due(
+    c: &PgConnection,
+    now: chrono::NaiveDateTime,
+    leased_until: chrono::NaiveDateTime,
+    batch_size: i64,
+) -> QueryResult<Vec<OutboxEmail>> {
+    c.transaction(|| {
+        let due = email_outbox::table
+            .select(email_outbox::id)
+            .filter(email_outbox::status.eq(OutboxStatus::Pending.as_str()))
+            .filter(email_outbox::next_attempt_at.le(now))
+            .order(email_outbox::next_attempt_at.asc())
+            .limit(batch_size)
+            .for_update()
+            .skip_locked()
+            .load::<i32>(c)?;
+
+        diesel::update(email_outbox::table.filter(email_outbox::id.eq_any(due)))
+            .set(email_outbox::next_attempt_at.eq(leased_until))
+            .get_results::<OutboxEmail>(c)
+    })
+}
+
+/// Delivered emails are removed right away, their links should not outlive delivery.
+pub fn delete(c: &PgConnection, id: i32) -> QueryResult<usize> {
+    diesel::delete(email_outbox::table.find(id)).execute(c)
+}
+
+/// Deletes up to `batch_size` emails that died before `dead_before`, a dead email's
+/// `next_attempt_at` being the time it died.
+pub fn delete_dead_batch(
+    c: &PgConnection,
+    dead_before: chrono::NaiveDateTime,
+    batch_size: i64,
+) -> QueryResult<usize> {
+    let expired = email_outbox::table
+        .select(email_outbox::id)
+        .filter(email_outbox::status.eq(OutboxStatus::Dead.as_str()))
+        .filter(email_outbox::next_attempt_at.lt(dead_before))
+        .limit(batch_size)
+        .load::<i32>(c)?;
+    diesel::delete(email_outbox::table.filter(email_outbox::id.eq_any(expired))).execute(c)
+}
+
+/// Schedules the next attempt, or marks the email dead when there is none.
+pub fn record_failure(
+    c: &PgConnection,
+    id: i32,
+    attempts: i32,
+    error: String,
+    next_attempt_at: Option<chrono::NaiveDateTime>,
+) -> QueryResult<usize> {
+    let status = match next_attempt_at {
+        Some(_) => OutboxStatus::Pending,
+        None => OutboxStatus::Dead,
+    };
+
+    diesel::update(email_outbox::table.find(id))
+        .set((
+            email_outbox::status.eq(status.as_str()),
+            email_outbox::attempts.eq(attempts),
+            email_outbox::last_error.eq(error),
+            email_outbox::next_attempt_at
+                .eq(next_attempt_at.unwrap_or_else(|| chrono::Utc::now().naive_utc())),
+        ))
+        .execute(c)
+}
+
+pub async fn find_by_status(
+    conn: &DbConn,
+    status: OutboxStatus,
+    limit: i64,
+) -> Result<Vec<OutboxEmail>, crate::util::response::Error> {
+    conn.run(move |c| {
+        email_outbox::table
+            .filter(email_outbox::status.eq(status.as_str()))
+            .order(email_outbox::id.asc())
+            .limit(limit)
+            .load::<OutboxEmail>(c)
+            .map_err(get_auth_error_response)
+    })
+    .await
+}
+
+/// Moves a dead email back to pending with a fresh set of attempts.
+pub async fn retry(conn: &DbConn, id: i32) -> Result<usize, crate::util::response::Error> {
+    conn.run(move |c| {
+        let dead_email = email_outbox::table
+            .find(id)
+            .filter(email_outbox::status.eq(OutboxStatus::Dead.as_str()));
+        diesel::update(dead_email)
+            .set((
+                email_outbox::status.eq(OutboxStatus::Pending.as_str()),
+                email_outbox::attempts.eq(0),
+                email_outbox::next_attempt_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .execute(c)
+            .map_err(get_auth_error_response)
+    })
+    .await
+}
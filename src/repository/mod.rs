@@ -1,2 +1,12 @@
+pub mod account_export;
+pub mod account_tombstone;
+pub mod email_change_request;
+pub mod email_outbox;
+pub mod password_reset_token;
 pub mod refresh_token;
+pub mod revoked_token;
+pub mod security_event;
+pub mod signing_key_promotion;
 pub mod user;
+pub mod user_identity;
+pub mod username_history;
@@ -0,0 +1,74 @@
+use crate::models::{
+    email_outbox::NewOutboxEmail,
+    password_reset_token::{NewPasswordResetToken, PasswordResetToken},
+};
+use crate::repository::{email_outbox, refresh_token, user};
+use crate::schema::password_reset_tokens;
+use crate::{database::DbConn, routes::users_util::get_auth_error_response};
+use rocket_sync_db_pools::diesel::{self, prelude::*};
+
+/// Stores the token and queues `emails`, so the reset link is only sent for a stored token.
+pub async fn insert(
+    conn: &DbConn,
+    reset_token: NewPasswordResetToken,
+    emails: Vec<NewOutboxEmail>,
+) -> Result<usize, crate::util::response::Error> {
+    conn.run(|c| {
+        c.transaction(|| {
+            let inserted = diesel::insert_into(password_reset_tokens::table)
+                .values(reset_token)
+                .execute(c)?;
+            email_outbox::queue(c, emails)?;
+
+            Ok(inserted)
+        })
+        .map_err(get_auth_error_response)
+    })
+    .await
+}
+
+/// Marks an unused, unexpired token as used. Returns `None` when there is no such token,
+/// so a token can only be redeemed once.
+fn consume(c: &PgConnection, token_hash: String) -> QueryResult<Option<PasswordResetToken>> {
+    let now = chrono::Utc::now().naive_utc();
+    let redeemable_token = password_reset_tokens::table
+        .filter(password_reset_tokens::token_hash.eq(token_hash))
+        .filter(password_reset_tokens::used_at.is_null())
+        .filter(password_reset_tokens::expiry.gt(now));
+    diesel::update(redeemable_token)
+        .set(password_reset_tokens::used_at.eq(now))
+        .get_result::<PasswordResetToken>(c)
+        .optional()
+}
+
+/// Consumes the token, sets `password` and deletes the user's refresh and reset tokens in
+/// one transaction. Returns the user id and the number of revoked refresh tokens, or
+/// `None` when the token cannot be redeemed.
+pub async fn redeem(
+    conn: &DbConn,
+    token_hash: String,
+    password: String,
+) -> Result<Option<(i32, usize)>, crate::util::response::Error> {
+    conn.run(move |c| {
+        c.transaction(|| {
+            let reset_token = match consume(c, token_hash)? {
+                Some(reset_token) => reset_token,
+                None => return Ok(None),
+            };
+            let user_id = reset_token.user_id;
+
+            user::set_password(c, user_id, password)?;
+            let revoked = refresh_token::delete_user_tokens(c, user_id)?;
+            delete_user_tokens(c, user_id)?;
+
+            Ok(Some((user_id, revoked)))
+        })
+        .map_err(get_auth_error_response)
+    })
+    .await
+}
+
+pub fn delete_user_tokens(c: &PgConnection, user_id: i32) -> QueryResult<usize> {
+    diesel::delete(password_reset_tokens::table.filter(password_reset_tokens::user_id.eq(user_id)))
+        .execute(c)
+}
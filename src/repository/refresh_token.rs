@@ -1,29 +1,41 @@
-use crate::models::user::{NewRefreshToken, RefreshToken};
+use crate::models::{
+    email_outbox::NewOutboxEmail,
+    user::{NewRefreshToken, RefreshToken},
+};
+use crate::repository::email_outbox;
 use crate::schema::refresh_tokens;
 use crate::{database::DbConn, routes::users_util::get_auth_error_response};
 use rocket::{http::Status, info};
 use rocket_sync_db_pools::diesel::{self, prelude::*};
 
+/// Stores the token and queues `emails` in one transaction, so a sign-in alert is sent
+/// exactly when the session it reports exists.
 pub async fn insert(
     conn: &DbConn,
     refresh_token: NewRefreshToken,
+    emails: Vec<NewOutboxEmail>,
 ) -> Result<usize, crate::util::response::Error> {
     conn.run(|c| {
-        diesel::insert_into(refresh_tokens::table)
-            .values(refresh_token)
-            .execute(c)
-            .map_err(get_auth_error_response)
+        c.transaction(|| {
+            let inserted = diesel::insert_into(refresh_tokens::table)
+                .values(refresh_token)
+                .execute(c)?;
+            email_outbox::queue(c, emails)?;
+
+            Ok(inserted)
+        })
+        .map_err(get_auth_error_response)
     })
     .await
 }
 
 pub async fn find_by_token(
     conn: &DbConn,
-    token: String,
+    token_hash: String,
 ) -> Result<RefreshToken, crate::util::response::Error> {
     conn.run(move |c| {
         refresh_tokens::table
-            .filter(refresh_tokens::token.eq(token))
+            .filter(refresh_tokens::token_hash.eq(token_hash))
             .first::<RefreshToken>(c)
             .map_err(|e| {
                 info!("before mapping error {:?}", e);
@@ -48,3 +60,129 @@ pub async fn delete(conn: &DbConn, id: i32) -> Result<usize, crate::util::respon
     })
     .await
 }
+
+/// Returns false when the token was already rotated, which means it is being replayed.
+pub async fn mark_rotated(conn: &DbConn, id: i32) -> Result<bool, crate::util::response::Error> {
+    conn.run(move |c| {
+        let unrotated_token = refresh_tokens::table
+            .filter(refresh_tokens::id.eq(id))
+            .filter(refresh_tokens::rotated_at.is_null());
+        diesel::update(unrotated_token)
+            .set(refresh_tokens::rotated_at.eq(chrono::Utc::now().naive_utc()))
+            .execute(c)
+            .map(|updated| updated == 1)
+            .map_err(get_auth_error_response)
+    })
+    .await
+}
+
+pub async fn delete_family(
+    conn: &DbConn,
+    family_id: String,
+) -> Result<usize, crate::util::response::Error> {
+    conn.run(move |c| {
+        let family = refresh_tokens::table.filter(refresh_tokens::family_id.eq(family_id));
+        diesel::delete(family)
+            .execute(c)
+            .map_err(get_auth_error_response)
+    })
+    .await
+}
+
+/// The current (unrotated, unexpired) token of every session the user has.
+pub async fn find_sessions(
+    conn: &DbConn,
+    user_id: i32,
+) -> Result<Vec<RefreshToken>, crate::util::response::Error> {
+    conn.run(move |c| {
+        refresh_tokens::table
+            .filter(refresh_tokens::user_id.eq(user_id))
+            .filter(refresh_tokens::rotated_at.is_null())
+            .filter(refresh_tokens::expiry.gt(chrono::Utc::now().naive_utc()))
+            .order(refresh_tokens::last_used_at.desc())
+            .load::<RefreshToken>(c)
+            .map_err(get_auth_error_response)
+    })
+    .await
+}
+
+pub async fn delete_user_family(
+    conn: &DbConn,
+    user_id: i32,
+    family_id: String,
+) -> Result<usize, crate::util::response::Error> {
+    conn.run(move |c| {
+        let family = refresh_tokens::table
+            .filter(refresh_tokens::user_id.eq(user_id))
+            .filter(refresh_tokens::family_id.eq(family_id));
+        diesel::delete(family)
+            .execute(c)
+            .map_err(get_auth_error_response)
+    })
+    .await
+}
+
+pub async fn delete_other_families(
+    conn: &DbConn,
+    user_id: i32,
+    keep_family_id: Option<String>,
+) -> Result<usize, crate::util::response::Error> {
+    conn.run(move |c| {
+        delete_other_user_families(c, user_id, keep_family_id).map_err(get_auth_error_response)
+    })
+    .await
+}
+
+pub fn delete_other_user_families(
+    c: &PgConnection,
+    user_id: i32,
+    keep_family_id: Option<String>,
+) -> QueryResult<usize> {
+    let user_tokens = refresh_tokens::table.filter(refresh_tokens::user_id.eq(user_id));
+    match keep_family_id {
+        Some(family_id) => {
+            diesel::delete(user_tokens.filter(refresh_tokens::family_id.ne(family_id))).execute(c)
+        }
+        None => diesel::delete(user_tokens).execute(c),
+    }
+}
+
+pub fn delete_expired_batch(
+    c: &PgConnection,
+    now: chrono::NaiveDateTime,
+    batch_size: i64,
+) -> QueryResult<usize> {
+    let expired = refresh_tokens::table
+        .select(refresh_tokens::id)
+        .filter(refresh_tokens::expiry.lt(now))
+        .limit(batch_size)
+        .load::<i32>(c)?;
+    diesel::delete(refresh_tokens::table.filter(refresh_tokens::id.eq_any(expired))).execute(c)
+}
+
+/// Rotated tokens are kept to detect reuse while their session is alive; once the
+/// session's current token is revoked they are no longer needed.
+pub fn delete_revoked_batch(
+    c: &PgConnection,
+    rotated_before: chrono::NaiveDateTime,
+    batch_size: i64,
+) -> QueryResult<usize> {
+    diesel::sql_query(
+        "DELETE FROM refresh_tokens WHERE id IN ( \
+            SELECT r.id FROM refresh_tokens r \
+            WHERE r.rotated_at < $1 \
+            AND NOT EXISTS ( \
+                SELECT 1 FROM refresh_tokens a \
+                WHERE a.family_id = r.family_id AND a.rotated_at IS NULL \
+            ) \
+            LIMIT $2 \
+        )",
+    )
+    .bind::<diesel::sql_types::Timestamp, _>(rotated_before)
+    .bind::<diesel::sql_types::BigInt, _>(batch_size)
+    .execute(c)
+}
+
+pub fn delete_user_tokens(c: &PgConnection, user_id: i32) -> QueryResult<usize> {
+    diesel::delete(refresh_tokens::table.filter(refresh_tokens::user_id.eq(user_id))).execute(c)
+}
@@ -0,0 +1,52 @@
+use crate::models::revoked_token::NewRevokedToken;
+use crate::schema::revoked_tokens;
+use crate::{database::DbConn, routes::users_util::get_auth_error_response};
+use rocket_sync_db_pools::diesel::{self, prelude::*};
+
+pub async fn insert(
+    conn: &DbConn,
+    revoked_token: NewRevokedToken,
+) -> Result<usize, crate::util::response::Error> {
+    conn.run(|c| revoke(c, revoked_token).map_err(get_auth_error_response))
+        .await
+}
+
+pub fn revoke(c: &PgConnection, revoked_token: NewRevokedToken) -> QueryResult<usize> {
+    diesel::insert_into(revoked_tokens::table)
+        .values(revoked_token)
+        .on_conflict(revoked_tokens::jti)
+        .do_nothing()
+        .execute(c)
+}
+
+pub async fn is_revoked(conn: &DbConn, jti: String) -> QueryResult<bool> {
+    conn.run(move |c| {
+        diesel::select(diesel::dsl::exists(
+            revoked_tokens::table.filter(revoked_tokens::jti.eq(jti)),
+        ))
+        .get_result(c)
+    })
+    .await
+}
+
+pub async fn delete_expired(conn: &DbConn) -> QueryResult<usize> {
+    conn.run(|c| {
+        let expired =
+            revoked_tokens::table.filter(revoked_tokens::expiry.lt(chrono::Utc::now().naive_utc()));
+        diesel::delete(expired).execute(c)
+    })
+    .await
+}
+
+pub fn delete_expired_batch(
+    c: &PgConnection,
+    now: chrono::NaiveDateTime,
+    batch_size: i64,
+) -> QueryResult<usize> {
+    let expired = revoked_tokens::table
+        .select(revoked_tokens::id)
+        .filter(revoked_tokens::expiry.lt(now))
+        .limit(batch_size)
+        .load::<i32>(c)?;
+    diesel::delete(revoked_tokens::table.filter(revoked_tokens::id.eq_any(expired))).execute(c)
+}
@@ -0,0 +1,39 @@
+use crate::models::security_event::{NewSecurityEvent, SecurityEvent};
+use crate::schema::security_events;
+use crate::{database::DbConn, routes::users_util::get_auth_error_response};
+use rocket::warn;
+use rocket_sync_db_pools::diesel::{self, prelude::*};
+
+pub async fn insert(
+    conn: &DbConn,
+    security_event: NewSecurityEvent,
+) -> Result<usize, crate::util::response::Error> {
+    conn.run(|c| record(c, security_event).map_err(get_auth_error_response))
+        .await
+}
+
+/// Inserts on `c`, so the event can be recorded in the transaction of the change.
+pub fn record(c: &PgConnection, security_event: NewSecurityEvent) -> QueryResult<usize> {
+    warn!(
+        "security event {} for user {}",
+        security_event.event_type, security_event.user_id
+    );
+
+    diesel::insert_into(security_events::table)
+        .values(security_event)
+        .execute(c)
+}
+
+pub async fn find_for_user(
+    conn: &DbConn,
+    user_id: i32,
+) -> Result<Vec<SecurityEvent>, crate::util::response::Error> {
+    conn.run(move |c| {
+        security_events::table
+            .filter(security_events::user_id.eq(user_id))
+            .order(security_events::created_at.asc())
+            .load::<SecurityEvent>(c)
+            .map_err(get_auth_error_response)
+    })
+    .await
+}
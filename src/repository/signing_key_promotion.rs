@@ -0,0 +1,27 @@
+use crate::schema::signing_key_promotions;
+use crate::{database::DbConn, routes::users_util::get_auth_error_response};
+use rocket_sync_db_pools::diesel::{self, prelude::*};
+
+pub async fn insert(conn: &DbConn, kid: String) -> Result<usize, crate::util::response::Error> {
+    conn.run(move |c| {
+        diesel::insert_into(signing_key_promotions::table)
+            .values(signing_key_promotions::kid.eq(kid))
+            .execute(c)
+            .map_err(get_auth_error_response)
+    })
+    .await
+}
+
+/// The most recently promoted of `kids`, promotions of keys this instance does not know
+/// are ignored.
+pub fn find_latest(c: &PgConnection, kids: Vec<String>) -> QueryResult<Option<String>> {
+    signing_key_promotions::table
+        .select(signing_key_promotions::kid)
+        .filter(signing_key_promotions::kid.eq_any(kids))
+        .order((
+            signing_key_promotions::promoted_at.desc(),
+            signing_key_promotions::id.desc(),
+        ))
+        .first(c)
+        .optional()
+}
@@ -1,81 +1,431 @@
-use crate::models::user::{NewUser, NewUserRequest, User};
-use crate::schema::users;
+use crate::models::{
+    account_tombstone::NewAccountTombstone,
+    email_outbox::NewOutboxEmail,
+    revoked_token::NewRevokedToken,
+    security_event::{NewSecurityEvent, SecurityEventType},
+    user::{NewUser, NewUserRequest, User},
+    user_identity::NewUserIdentity,
+    username_history::NewUsernameHistory,
+};
+use crate::repository::{
+    email_outbox, password_reset_token, refresh_token, revoked_token, security_event,
+    username_history::{find_latest_change, is_held},
+};
+use crate::schema::{
+    account_tombstones, refresh_tokens, security_events, user_identities, username_history, users,
+};
 use crate::{
-    database::DbConn,
+    database::{lower, DbConn},
     routes::users_util::get_auth_error_response,
     util::response::{Error, ErrorType},
 };
 use rocket::http::Status;
-use rocket_sync_db_pools::diesel::{self, prelude::*};
+use rocket_sync_db_pools::diesel::{
+    self,
+    prelude::*,
+    result::{DatabaseErrorKind, Error as DieselError},
+};
 
 pub fn get_by_username(username: &str, conn: &PgConnection) -> QueryResult<i32> {
     users::table
         .select(users::id)
-        .filter(users::username.eq(&username))
+        .filter(lower(users::username).eq(username.to_lowercase()))
         .get_result::<i32>(conn)
 }
 
 pub fn get_by_email(email: &str, conn: &PgConnection) -> QueryResult<i32> {
     users::table
         .select(users::id)
-        .filter(users::email.eq(&email))
+        .filter(lower(users::email).eq(email.to_lowercase()))
         .get_result::<i32>(conn)
 }
 
+/// Taken by another account, or still held for the account that gave it up.
+fn is_username_taken(username: &str, user_id: Option<i32>, conn: &PgConnection) -> bool {
+    let is_current_name = get_by_username(username, conn).is_ok_and(|id| Some(id) != user_id);
+
+    is_current_name || is_held(username, user_id, conn).unwrap_or(true)
+}
+
+fn check_duplicates(
+    username: Option<&str>,
+    email: Option<&str>,
+    conn: &PgConnection,
+) -> Result<(), crate::util::response::Error> {
+    let is_found_by_username = username.is_some_and(|u| is_username_taken(u, None, conn));
+    let is_found_by_email = email.is_some_and(|e| get_by_email(e, conn).is_ok());
+
+    let mut errors: Vec<String> = vec![];
+
+    if is_found_by_username {
+        errors.push("username_exists".to_owned());
+    }
+
+    if is_found_by_email {
+        errors.push("email_exists".to_owned());
+    }
+
+    if !errors.is_empty() {
+        return Err(Error::error(
+            Some((errors, ErrorType::RequestInvalid)),
+            Status::Conflict,
+        ));
+    }
+
+    Ok(())
+}
+
+/// The case-insensitive unique indexes on `users` catch registrations and renames that race
+/// past `check_duplicates`; report them with the same codes.
+fn get_unique_violation_response(error: DieselError) -> Error {
+    let code = match &error {
+        DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, info) => {
+            match info.constraint_name() {
+                Some("users_username_lower_key") => "username_exists",
+                Some("users_email_lower_key") => "email_exists",
+                _ => return get_auth_error_response(error),
+            }
+        }
+        _ => return get_auth_error_response(error),
+    };
+
+    Error::error(
+        Some((vec![code.to_owned()], ErrorType::RequestInvalid)),
+        Status::Conflict,
+    )
+}
+
 pub async fn is_duplicate_user_or_email(
     conn: &DbConn,
     user: NewUserRequest,
 ) -> Result<NewUserRequest, crate::util::response::Error> {
     conn.run(|c| {
-        let found_username = get_by_username(&user.username.to_owned().unwrap(), c);
-        let found_email = get_by_email(&user.email.to_owned().unwrap(), c);
+        check_duplicates(user.username.as_deref(), user.email.as_deref(), c)?;
 
-        let is_found_by_username = found_username.is_ok();
-        let is_found_by_email = found_email.is_ok();
+        Ok(user)
+    })
+    .await
+}
 
-        let mut errors: Vec<String> = vec![];
+pub async fn is_duplicate_email(
+    conn: &DbConn,
+    email: String,
+) -> Result<String, crate::util::response::Error> {
+    conn.run(|c| {
+        check_duplicates(None, Some(&email), c)?;
 
-        if is_found_by_username {
-            errors.push("username_exists".to_owned());
-        }
+        Ok(email)
+    })
+    .await
+}
 
-        if is_found_by_email {
-            errors.push("email_exists".to_owned());
-        }
+/// Renames the user `user_id` unless it was last renamed after `changed_before`, and
+/// records the old name, which stays reserved until `held_until`. The user row is locked
+/// so concurrent renames cannot both pass the check.
+pub async fn change_username(
+    conn: &DbConn,
+    user_id: i32,
+    username: String,
+    changed_before: chrono::NaiveDateTime,
+    held_until: chrono::NaiveDateTime,
+) -> Result<User, crate::util::response::Error> {
+    conn.run(move |c| {
+        c.transaction(|| {
+            let user = users::table.find(user_id).for_update().first::<User>(c)?;
 
-        if !errors.is_empty() {
-            return Err(Error::error(
-                Some((errors, ErrorType::RequestInvalid)),
-                Status::Conflict,
-            ));
-        }
+            if let Some(last_change) = find_latest_change(c, user.id)? {
+                if last_change.changed_at > changed_before {
+                    return Err(Error::error(
+                        Some((
+                            vec!["username_change_too_soon".to_owned()],
+                            ErrorType::RequestInvalid,
+                        )),
+                        Status::TooManyRequests,
+                    ));
+                }
+            }
 
-        Ok(user)
+            if is_username_taken(&username, Some(user.id), c) {
+                return Err(Error::error(
+                    Some((
+                        vec!["username_exists".to_owned()],
+                        ErrorType::RequestInvalid,
+                    )),
+                    Status::Conflict,
+                ));
+            }
+
+            diesel::insert_into(username_history::table)
+                .values(NewUsernameHistory {
+                    user_id: user.id,
+                    username: user.username,
+                    held_until,
+                })
+                .execute(c)?;
+
+            diesel::update(users::table.find(user.id))
+                .set(users::username.eq(username))
+                .get_result::<User>(c)
+                .map_err(get_unique_violation_response)
+        })
     })
     .await
 }
 
-pub async fn insert(conn: &DbConn, user: NewUser) -> Result<User, crate::util::response::Error> {
+/// Inserts the user and queues the emails `emails` builds for it in the same transaction.
+pub async fn insert(
+    conn: &DbConn,
+    user: NewUser,
+    emails: impl FnOnce(&User) -> Vec<NewOutboxEmail> + Send + 'static,
+) -> Result<User, crate::util::response::Error> {
     conn.run(|c| {
-        diesel::insert_into(users::table)
-            .values(user)
-            .get_result::<User>(c)
-            .map_err(get_auth_error_response)
+        c.transaction(|| {
+            let user = diesel::insert_into(users::table)
+                .values(user)
+                .get_result::<User>(c)
+                .map_err(get_unique_violation_response)?;
+            email_outbox::queue(c, emails(&user))?;
+
+            Ok(user)
+        })
+    })
+    .await
+}
+
+/// Numbered usernames tried before an identity's account creation gives up.
+const MAX_USERNAME_SUFFIX: u32 = 100;
+
+/// `username`, or the first of `username2`, `username3`, ... that is free. Lookup errors
+/// are returned rather than counted as taken, so a failing database ends the search.
+fn available_username(username: &str, conn: &PgConnection) -> Result<String, Error> {
+    let is_free = |candidate: &str| -> QueryResult<bool> {
+        let is_current_name = match get_by_username(candidate, conn) {
+            Ok(_) => true,
+            Err(DieselError::NotFound) => false,
+            Err(e) => return Err(e),
+        };
+
+        Ok(!is_current_name && !is_held(candidate, None, conn)?)
+    };
+
+    let candidates = std::iter::once(username.to_owned())
+        .chain((2..=MAX_USERNAME_SUFFIX).map(|n| format!("{}{}", username, n)));
+    for candidate in candidates {
+        if is_free(&candidate)? {
+            return Ok(candidate);
+        }
+    }
+
+    Err(Error::error(
+        Some((
+            vec!["username_exists".to_owned()],
+            ErrorType::RequestInvalid,
+        )),
+        Status::Conflict,
+    ))
+}
+
+/// Creates an account for an external identity. The provider vouches for the email
+/// address, so it counts as verified; a taken username gets a numeric suffix.
+pub async fn insert_with_identity(
+    conn: &DbConn,
+    user: NewUser,
+    provider: &'static str,
+    provider_user_id: String,
+) -> Result<User, crate::util::response::Error> {
+    conn.run(move |c| {
+        c.transaction(|| {
+            let user = NewUser {
+                username: available_username(&user.username, c)?,
+                ..user
+            };
+            let user = diesel::insert_into(users::table)
+                .values(user)
+                .get_result::<User>(c)
+                .map_err(get_unique_violation_response)?;
+
+            diesel::insert_into(user_identities::table)
+                .values(NewUserIdentity {
+                    user_id: user.id,
+                    provider: provider.to_owned(),
+                    provider_user_id,
+                })
+                .execute(c)?;
+
+            diesel::update(users::table.find(user.id))
+                .set(users::email_verified_at.eq(chrono::Utc::now().naive_utc()))
+                .get_result::<User>(c)
+                .map_err(Error::from)
+        })
     })
     .await
 }
 
+/// Deleted accounts are excluded from every lookup except `find_restorable`.
 pub async fn find(conn: &DbConn, identifier: String) -> Result<User, Status> {
     conn.run(move |c| {
         users::table
-            .filter(users::email.eq(&identifier))
-            .or_filter(users::username.eq(&identifier))
+            .filter(
+                lower(users::email)
+                    .eq(identifier.to_lowercase())
+                    .or(lower(users::username).eq(identifier.to_lowercase())),
+            )
+            .filter(users::is_deleted.eq(false))
+            .get_result::<User>(c)
+            .map_err(|_| Status::NotFound)
+    })
+    .await
+}
+
+/// A deleted account that is still within its grace period.
+pub async fn find_restorable(
+    conn: &DbConn,
+    identifier: String,
+    deleted_after: chrono::NaiveDateTime,
+) -> Result<User, Status> {
+    conn.run(move |c| {
+        users::table
+            .filter(
+                lower(users::email)
+                    .eq(identifier.to_lowercase())
+                    .or(lower(users::username).eq(identifier.to_lowercase())),
+            )
+            .filter(users::is_deleted.eq(true))
+            .filter(users::deleted_at.gt(deleted_after))
             .get_result::<User>(c)
             .map_err(|_| Status::NotFound)
     })
     .await
 }
 
+pub async fn find_by_id(conn: &DbConn, id: i32) -> Result<User, Status> {
+    conn.run(move |c| {
+        users::table
+            .find(id)
+            .filter(users::is_deleted.eq(false))
+            .get_result::<User>(c)
+            .map_err(|_| Status::NotFound)
+    })
+    .await
+}
+
+pub async fn find_by_username(conn: &DbConn, username: String) -> Result<User, Status> {
+    conn.run(move |c| {
+        users::table
+            .filter(lower(users::username).eq(username.to_lowercase()))
+            .filter(users::is_deleted.eq(false))
+            .get_result::<User>(c)
+            .map_err(|_| Status::NotFound)
+    })
+    .await
+}
+
+pub async fn find_by_email(conn: &DbConn, email: String) -> Result<User, Status> {
+    conn.run(move |c| {
+        users::table
+            .filter(lower(users::email).eq(email.to_lowercase()))
+            .filter(users::is_deleted.eq(false))
+            .get_result::<User>(c)
+            .map_err(|_| Status::NotFound)
+    })
+    .await
+}
+
+/// Sets `password`, ends every session but `keep_family_id` and queues `emails` in one
+/// transaction. Returns the number of revoked refresh tokens.
+pub async fn update_password(
+    conn: &DbConn,
+    id: i32,
+    password: String,
+    keep_family_id: Option<String>,
+    emails: Vec<NewOutboxEmail>,
+) -> Result<usize, crate::util::response::Error> {
+    conn.run(move |c| {
+        c.transaction(|| {
+            set_password(c, id, password)?;
+            let revoked = refresh_token::delete_other_user_families(c, id, keep_family_id)?;
+            email_outbox::queue(c, emails)?;
+
+            Ok(revoked)
+        })
+        .map_err(get_auth_error_response)
+    })
+    .await
+}
+
+pub fn set_password(c: &PgConnection, id: i32, password: String) -> QueryResult<usize> {
+    diesel::update(users::table.find(id))
+        .set(users::password.eq(password))
+        .execute(c)
+}
+
+/// Only verifies the address the token was issued for; returns 0 when it changed since.
+/// `emails` are only queued when the address is verified now.
+pub async fn mark_email_verified(
+    conn: &DbConn,
+    id: i32,
+    email: String,
+    emails: Vec<NewOutboxEmail>,
+) -> Result<usize, crate::util::response::Error> {
+    conn.run(move |c| {
+        c.transaction(|| {
+            let unverified_user = users::table
+                .find(id)
+                .filter(users::email.eq(email))
+                .filter(users::email_verified_at.is_null());
+            let updated = diesel::update(unverified_user)
+                .set(users::email_verified_at.eq(chrono::Utc::now().naive_utc()))
+                .execute(c)?;
+            if updated == 1 {
+                email_outbox::queue(c, emails)?;
+            }
+
+            Ok(updated)
+        })
+        .map_err(get_auth_error_response)
+    })
+    .await
+}
+
+/// A confirmed change proves ownership of the new address, so it counts as verified.
+/// Sets `email` as verified address of the user unless another account has it. Returns 0
+/// for a deleted user. The user row stays locked for the rest of the transaction.
+pub fn set_email(c: &PgConnection, id: i32, email: &str) -> Result<usize, Error> {
+    let active_user = users::table
+        .find(id)
+        .filter(users::is_deleted.eq(false))
+        .select(users::id)
+        .for_update()
+        .first::<i32>(c)
+        .optional()?;
+    if active_user.is_none() {
+        return Ok(0);
+    }
+
+    check_duplicates(None, Some(email), c)?;
+    diesel::update(users::table.find(id))
+        .set((
+            users::email.eq(email),
+            users::email_verified_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .execute(c)
+        .map_err(get_unique_violation_response)
+}
+
+pub async fn update_locale(
+    conn: &DbConn,
+    id: i32,
+    locale: Option<String>,
+) -> Result<usize, crate::util::response::Error> {
+    conn.run(move |c| {
+        diesel::update(users::table.find(id))
+            .set(users::locale.eq(locale))
+            .execute(c)
+            .map_err(get_auth_error_response)
+    })
+    .await
+}
+
 pub async fn update(conn: &DbConn, id: i32, user: User) -> QueryResult<User> {
     conn.run(move |c| {
         diesel::update(users::table.find(id))
@@ -85,13 +435,86 @@ pub async fn update(conn: &DbConn, id: i32, user: User) -> QueryResult<User> {
     .await
 }
 
-pub async fn delete(conn: &DbConn, id: i32, mut user: User) -> QueryResult<User> {
+/// Soft deletes the user and, in the same transaction, ends its sessions, drops its reset
+/// tokens, revokes `access_token`, records the event and queues `emails`. Returns the
+/// number of revoked refresh tokens.
+pub async fn delete(
+    conn: &DbConn,
+    id: i32,
+    access_token: NewRevokedToken,
+    emails: Vec<NewOutboxEmail>,
+) -> Result<usize, crate::util::response::Error> {
     conn.run(move |c| {
-        user.is_deleted = true;
+        c.transaction(|| {
+            diesel::update(users::table.find(id))
+                .set((
+                    users::is_deleted.eq(true),
+                    users::deleted_at.eq(chrono::Utc::now().naive_utc()),
+                ))
+                .execute(c)?;
+            let revoked = refresh_token::delete_user_tokens(c, id)?;
+            password_reset_token::delete_user_tokens(c, id)?;
+            revoked_token::revoke(c, access_token)?;
+            security_event::record(
+                c,
+                NewSecurityEvent::new(id, SecurityEventType::AccountDeleted, None),
+            )?;
+            email_outbox::queue(c, emails)?;
+
+            Ok(revoked)
+        })
+        .map_err(get_auth_error_response)
+    })
+    .await
+}
 
+pub async fn restore(conn: &DbConn, id: i32) -> Result<User, crate::util::response::Error> {
+    conn.run(move |c| {
         diesel::update(users::table.find(id))
-            .set(user)
-            .get_result(c)
+            .set((
+                users::is_deleted.eq(false),
+                users::deleted_at.eq(None::<chrono::NaiveDateTime>),
+            ))
+            .get_result::<User>(c)
+            .map_err(get_auth_error_response)
     })
     .await
 }
+
+/// Permanently removes accounts deleted before `deleted_before` together with their
+/// sessions and security events, leaving a tombstone for each. Reset tokens, email change
+/// requests, username history and linked identities are removed by cascade.
+pub fn purge_deleted_batch(
+    c: &PgConnection,
+    deleted_before: chrono::NaiveDateTime,
+    batch_size: i64,
+) -> QueryResult<usize> {
+    c.transaction(|| {
+        let purged_users = users::table
+            .select((users::id, users::deleted_at))
+            .filter(users::is_deleted.eq(true))
+            .filter(users::deleted_at.lt(deleted_before))
+            .limit(batch_size)
+            .for_update()
+            .load::<(i32, Option<chrono::NaiveDateTime>)>(c)?;
+        let user_ids: Vec<i32> = purged_users.iter().map(|(id, _)| *id).collect();
+
+        let tombstones: Vec<NewAccountTombstone> = purged_users
+            .into_iter()
+            .map(|(user_id, deleted_at)| NewAccountTombstone {
+                user_id,
+                deleted_at,
+            })
+            .collect();
+        diesel::insert_into(account_tombstones::table)
+            .values(&tombstones)
+            .on_conflict_do_nothing()
+            .execute(c)?;
+
+        diesel::delete(refresh_tokens::table.filter(refresh_tokens::user_id.eq_any(&user_ids)))
+            .execute(c)?;
+        diesel::delete(security_events::table.filter(security_events::user_id.eq_any(&user_ids)))
+            .execute(c)?;
+        diesel::delete(users::table.filter(users::id.eq_any(&user_ids))).execute(c)
+    })
+}
@@ -0,0 +1,68 @@
+use crate::models::{
+    security_event::{NewSecurityEvent, SecurityEventType},
+    user::User,
+    user_identity::{NewUserIdentity, UserIdentity},
+};
+use crate::repository::security_event;
+use crate::schema::{user_identities, users};
+use crate::{database::DbConn, routes::users_util::get_auth_error_response};
+use rocket_sync_db_pools::diesel::{self, prelude::*};
+
+/// The user an external identity signs in as, including deleted users so the caller can
+/// decide whether to restore them.
+pub async fn find_user(
+    conn: &DbConn,
+    provider: &'static str,
+    provider_user_id: String,
+) -> Result<Option<User>, crate::util::response::Error> {
+    conn.run(move |c| {
+        user_identities::table
+            .inner_join(users::table)
+            .select(users::all_columns)
+            .filter(user_identities::provider.eq(provider))
+            .filter(user_identities::provider_user_id.eq(provider_user_id))
+            .first::<User>(c)
+            .optional()
+            .map_err(get_auth_error_response)
+    })
+    .await
+}
+
+/// Links an identity to an existing account and records it as a security event.
+pub async fn link(
+    conn: &DbConn,
+    identity: NewUserIdentity,
+) -> Result<usize, crate::util::response::Error> {
+    conn.run(move |c| {
+        c.transaction(|| {
+            security_event::record(
+                c,
+                NewSecurityEvent::new(
+                    identity.user_id,
+                    SecurityEventType::IdentityLinked,
+                    Some(identity.provider.to_owned()),
+                ),
+            )?;
+
+            diesel::insert_into(user_identities::table)
+                .values(identity)
+                .execute(c)
+        })
+        .map_err(get_auth_error_response)
+    })
+    .await
+}
+
+pub async fn find_for_user(
+    conn: &DbConn,
+    user_id: i32,
+) -> Result<Vec<UserIdentity>, crate::util::response::Error> {
+    conn.run(move |c| {
+        user_identities::table
+            .filter(user_identities::user_id.eq(user_id))
+            .order(user_identities::created_at.asc())
+            .load::<UserIdentity>(c)
+            .map_err(get_auth_error_response)
+    })
+    .await
+}
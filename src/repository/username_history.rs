@@ -0,0 +1,64 @@
+use crate::models::username_history::UsernameHistory;
+use crate::schema::username_history;
+use crate::{
+    database::{lower, DbConn},
+    routes::users_util::get_auth_error_response,
+};
+use rocket_sync_db_pools::diesel::{self, prelude::*};
+
+/// Whether `username` is still reserved for a previous owner other than `user_id`.
+pub fn is_held(username: &str, user_id: Option<i32>, conn: &PgConnection) -> QueryResult<bool> {
+    let held = username_history::table
+        .filter(lower(username_history::username).eq(username.to_lowercase()))
+        .filter(username_history::held_until.gt(chrono::Utc::now().naive_utc()));
+
+    match user_id {
+        Some(id) => diesel::select(diesel::dsl::exists(
+            held.filter(username_history::user_id.ne(id)),
+        ))
+        .get_result(conn),
+        None => diesel::select(diesel::dsl::exists(held)).get_result(conn),
+    }
+}
+
+pub fn find_latest_change(
+    conn: &PgConnection,
+    user_id: i32,
+) -> QueryResult<Option<UsernameHistory>> {
+    username_history::table
+        .filter(username_history::user_id.eq(user_id))
+        .order(username_history::changed_at.desc())
+        .first::<UsernameHistory>(conn)
+        .optional()
+}
+
+/// The user who most recently gave up `username`.
+pub async fn find_previous_owner(
+    conn: &DbConn,
+    username: String,
+) -> Result<Option<i32>, crate::util::response::Error> {
+    conn.run(move |c| {
+        username_history::table
+            .select(username_history::user_id)
+            .filter(lower(username_history::username).eq(username.to_lowercase()))
+            .order(username_history::changed_at.desc())
+            .first::<i32>(c)
+            .optional()
+            .map_err(get_auth_error_response)
+    })
+    .await
+}
+
+pub async fn find_for_user(
+    conn: &DbConn,
+    user_id: i32,
+) -> Result<Vec<UsernameHistory>, crate::util::response::Error> {
+    conn.run(move |c| {
+        username_history::table
+            .filter(username_history::user_id.eq(user_id))
+            .order(username_history::changed_at.asc())
+            .load::<UsernameHistory>(c)
+            .map_err(get_auth_error_response)
+    })
+    .await
+}
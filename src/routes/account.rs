@@ -0,0 +1,118 @@
+use rocket::{
+    delete,
+    http::{Cookie, CookieJar, Status},
+    info, put,
+    serde::json::Json,
+    State,
+};
+
+use crate::{
+    database::DbConn,
+    email_sender::LocalizedMailer,
+    email_templates::AccountDeletedEmail,
+    models::user::{DeleteAccountRequest, LocaleRequest},
+    repository::user,
+    util::{
+        authorization::AccessToken,
+        globals::{GlobalConfig, JWTConfig, COOKIE_REFRESH_TOKEN_NAME},
+        locale::Locale,
+        response::{Error, ErrorType},
+        validator::Validator,
+    },
+};
+
+use super::users_util::{
+    get_access_token_claims, revoked_access_token, verify_non_hashed_password,
+};
+
+/// Soft deletes the signed in account and ends all of its sessions. Logging in again within
+/// `account_deletion_grace_period` restores it.
+#[delete("/account", format = "application/json", data = "<request>")]
+pub async fn delete_account<'a>(
+    conn: DbConn,
+    request: Json<DeleteAccountRequest>,
+    access_token: AccessToken,
+    cookies: &'a CookieJar<'a>,
+    global_config: &State<GlobalConfig>,
+    mailer: LocalizedMailer<'_>,
+    jwt_config: &State<JWTConfig>,
+) -> Result<Status, Error> {
+    let request = request.into_inner();
+
+    request.validate_model()?;
+
+    let claims = get_access_token_claims(&access_token, jwt_config)?;
+    let user_id = claims.user_id().ok_or_else(Error::unauthorized)?;
+    let found_user = user::find_by_id(&conn, user_id)
+        .await
+        .map_err(Error::Error)?;
+
+    if !verify_non_hashed_password(
+        &found_user,
+        &request.password.unwrap(),
+        &global_config.auth_secret_key,
+    ) {
+        return Err(Error::error(
+            Some((
+                vec!["password_invalid".to_owned()],
+                ErrorType::RequestInvalid,
+            )),
+            Status::Forbidden,
+        ));
+    }
+
+    let notice = mailer.render(
+        found_user.id,
+        found_user.email.to_owned(),
+        found_user.locale.as_deref(),
+        &AccountDeletedEmail {
+            username: found_user.username.to_owned(),
+            grace_period_days: global_config.account_deletion_grace_period / 86400,
+        },
+    );
+    let revoked = user::delete(
+        &conn,
+        found_user.id,
+        revoked_access_token(&claims),
+        notice.into_iter().collect(),
+    )
+    .await?;
+    cookies.remove_private(Cookie::named(COOKIE_REFRESH_TOKEN_NAME));
+
+    info!(
+        "deleted user {}, revoked {} refresh tokens",
+        found_user.id, revoked
+    );
+
+    Ok(Status::NoContent)
+}
+
+/// Stores the locale emails to the signed in user are written in.
+#[put("/account/locale", format = "application/json", data = "<request>")]
+pub async fn update_locale(
+    conn: DbConn,
+    request: Json<LocaleRequest>,
+    access_token: AccessToken,
+    jwt_config: &State<JWTConfig>,
+) -> Result<Status, Error> {
+    let claims = get_access_token_claims(&access_token, jwt_config)?;
+    let user_id = claims.user_id().ok_or_else(Error::unauthorized)?;
+
+    let locale = match &request.locale {
+        Some(locale) => Some(Locale::parse(locale).ok_or_else(|| {
+            Error::error(
+                Some((
+                    vec!["locale_unsupported".to_owned()],
+                    ErrorType::RequestInvalid,
+                )),
+                Status::UnprocessableEntity,
+            )
+        })?),
+        None => None,
+    };
+
+    match user::update_locale(&conn, user_id, locale.map(|l| l.as_str().to_owned())).await? {
+        0 => Err(Error::unauthorized()),
+        _ => Ok(Status::NoContent),
+    }
+}
@@ -0,0 +1,214 @@
+use rocket::{
+    error,
+    http::{Header, Status},
+    post,
+    serde::json::{serde_json, Json},
+    Responder, State,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::DbConn,
+    models::{
+        account_export::NewAccountExport, security_event::SecurityEvent, user::User,
+        user_identity::UserIdentity, username_history::UsernameHistory,
+    },
+    repository::{
+        account_export, email_change_request, refresh_token, security_event, user, user_identity,
+        username_history,
+    },
+    util::{
+        authorization::AccessToken,
+        globals::{GlobalConfig, JWTConfig},
+        random::random_token,
+        response::{Error, ErrorType, Response},
+        token_hash::hash_token,
+    },
+};
+
+use super::{sessions::SessionResponse, users_util::get_access_token_claims};
+
+/// Exports still unfinished this long after the request were interrupted, e.g. by a
+/// restart, and will not complete.
+const EXPORT_TIMEOUT_SECONDS: i64 = 600;
+
+#[derive(Debug, Serialize)]
+pub struct ExportResponse {
+    token: String,
+    expires_at: chrono::NaiveDateTime,
+}
+
+/// Everything stored about an account except credentials and token hashes.
+#[derive(Debug, Serialize)]
+pub struct AccountArchive {
+    generated_at: chrono::NaiveDateTime,
+    account: AccountData,
+    sessions: Vec<SessionResponse>,
+    username_history: Vec<UsernameHistory>,
+    pending_email_change: Option<PendingEmailChange>,
+    security_events: Vec<SecurityEvent>,
+    identities: Vec<UserIdentity>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccountData {
+    id: i32,
+    username: String,
+    email: String,
+    email_verified_at: Option<chrono::NaiveDateTime>,
+    locale: Option<String>,
+    created_at: chrono::NaiveDateTime,
+    updated_at: chrono::NaiveDateTime,
+}
+
+impl AccountData {
+    pub fn from(user: User) -> Self {
+        Self {
+            id: user.id,
+            username: user.username,
+            email: user.email,
+            email_verified_at: user.email_verified_at,
+            locale: user.locale,
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PendingEmailChange {
+    new_email: String,
+    created_at: chrono::NaiveDateTime,
+    expiry: chrono::NaiveDateTime,
+}
+
+#[derive(Deserialize)]
+pub struct ExportDownloadRequest {
+    token: String,
+}
+
+#[derive(Responder)]
+pub enum ExportDownload {
+    #[response(status = 200, content_type = "json")]
+    Ready(String, Header<'static>),
+    #[response(status = 202)]
+    Pending(()),
+}
+
+async fn collect_archive(conn: &DbConn, user_id: i32) -> Result<AccountArchive, Error> {
+    let found_user = user::find_by_id(conn, user_id)
+        .await
+        .map_err(Error::Error)?;
+    let sessions = refresh_token::find_sessions(conn, user_id).await?;
+    let pending_email_change = email_change_request::find_for_user(conn, user_id).await?;
+
+    Ok(AccountArchive {
+        generated_at: chrono::Utc::now().naive_utc(),
+        account: AccountData::from(found_user),
+        sessions: sessions
+            .into_iter()
+            .map(|s| SessionResponse::from(s, None))
+            .collect(),
+        username_history: username_history::find_for_user(conn, user_id).await?,
+        pending_email_change: pending_email_change.map(|c| PendingEmailChange {
+            new_email: c.new_email,
+            created_at: c.created_at,
+            expiry: c.expiry,
+        }),
+        security_events: security_event::find_for_user(conn, user_id).await?,
+        identities: user_identity::find_for_user(conn, user_id).await?,
+    })
+}
+
+async fn build_archive(conn: &DbConn, export_id: i32, user_id: i32) -> Result<usize, Error> {
+    let archive = collect_archive(conn, user_id).await?;
+    let archive = serde_json::to_string_pretty(&archive).map_err(|e| {
+        error!("account export {} not serializable: {}", export_id, e);
+        Error::Error(Status::InternalServerError)
+    })?;
+
+    account_export::complete(conn, export_id, archive).await
+}
+
+async fn generate_archive(conn: DbConn, export_id: i32, user_id: i32) {
+    if let Err(e) = build_archive(&conn, export_id, user_id).await {
+        error!("account export {} failed: {:?}", export_id, e);
+        if account_export::fail(&conn, export_id).await.is_err() {
+            let _ = account_export::delete(&conn, export_id).await;
+        }
+    }
+}
+
+fn export_failed() -> Error {
+    Error::error(
+        Some((vec!["export_failed".to_owned()], ErrorType::RequestInvalid)),
+        Status::InternalServerError,
+    )
+}
+
+/// Starts assembling the signed in user's data. The returned token downloads the archive
+/// from `POST /account/export/download` once it is ready.
+#[post("/account/export")]
+pub async fn request_account_export(
+    conn: DbConn,
+    access_token: AccessToken,
+    global_config: &State<GlobalConfig>,
+    jwt_config: &State<JWTConfig>,
+) -> Result<Response<ExportResponse>, Error> {
+    let claims = get_access_token_claims(&access_token, jwt_config)?;
+    let user_id = claims.user_id().ok_or_else(Error::unauthorized)?;
+
+    let token = random_token();
+    let export = account_export::replace_for_user(
+        &conn,
+        NewAccountExport {
+            user_id,
+            token_hash: hash_token(&token, global_config.token_hash_key()),
+            expiry: chrono::Utc::now().naive_utc()
+                + chrono::Duration::seconds(global_config.account_export_expiry),
+        },
+    )
+    .await?;
+
+    let expires_at = export.expiry;
+    rocket::tokio::spawn(generate_archive(conn, export.id, user_id));
+
+    Ok(Response::success(
+        Some(ExportResponse { token, expires_at }),
+        Status::Accepted,
+    ))
+}
+
+/// The token comes in the body so it does not end up in access logs.
+#[post(
+    "/account/export/download",
+    format = "application/json",
+    data = "<request>"
+)]
+pub async fn download_account_export(
+    conn: DbConn,
+    request: Json<ExportDownloadRequest>,
+    global_config: &State<GlobalConfig>,
+) -> Result<ExportDownload, Error> {
+    let token_hash = hash_token(&request.token, global_config.token_hash_key());
+    let export = account_export::find_by_token(&conn, token_hash)
+        .await?
+        .ok_or(Error::Error(Status::NotFound))?;
+    let timed_out = export.created_at
+        < chrono::Utc::now().naive_utc() - chrono::Duration::seconds(EXPORT_TIMEOUT_SECONDS);
+
+    match export.archive {
+        Some(archive) => Ok(ExportDownload::Ready(
+            archive,
+            Header::new(
+                "Content-Disposition",
+                format!(
+                    "attachment; filename=\"account-export-{}.json\"",
+                    export.user_id
+                ),
+            ),
+        )),
+        None if export.failed_at.is_some() || timed_out => Err(export_failed()),
+        None => Ok(ExportDownload::Pending(())),
+    }
+}
@@ -0,0 +1,140 @@
+use rocket::{http::Status, info, post, serde::json::Json, State};
+use serde::Deserialize;
+
+use crate::{
+    database::DbConn,
+    email_sender::{token_link, LocalizedMailer},
+    email_templates::{EmailChangeConfirmEmail, EmailChangeNoticeEmail},
+    models::{
+        email_change_request::NewEmailChangeRequest,
+        user::{normalize_email, EmailRequest},
+    },
+    repository::{
+        email_change_request::{confirm, delete_by_cancel_token, replace_for_user},
+        user::{find_by_id, is_duplicate_email},
+    },
+    util::{
+        authorization::AccessToken,
+        globals::{EmailConfig, GlobalConfig, JWTConfig},
+        random::random_token,
+        response::{Error, ErrorType},
+        token_hash::hash_token,
+        validator::Validator,
+    },
+};
+
+use super::users_util::get_access_token_claims;
+
+#[derive(Deserialize)]
+pub struct EmailChangeTokenRequest {
+    token: String,
+}
+
+fn invalid_token() -> Error {
+    Error::error(
+        Some((vec!["token_invalid".to_owned()], ErrorType::RequestInvalid)),
+        Status::BadRequest,
+    )
+}
+
+/// Emails a confirmation link to the new address and a cancel link to the current one.
+/// The address only changes once the new one is confirmed.
+#[post("/email/change", format = "application/json", data = "<request>")]
+pub async fn request_email_change(
+    conn: DbConn,
+    request: Json<EmailRequest>,
+    access_token: AccessToken,
+    global_config: &State<GlobalConfig>,
+    email_config: &State<EmailConfig>,
+    mailer: LocalizedMailer<'_>,
+    jwt_config: &State<JWTConfig>,
+) -> Result<Status, Error> {
+    let request = request.into_inner();
+
+    request.validate_model()?;
+
+    let claims = get_access_token_claims(&access_token, jwt_config)?;
+    let user_id = claims.user_id().ok_or_else(Error::unauthorized)?;
+    let user = find_by_id(&conn, user_id).await.map_err(Error::Error)?;
+
+    let new_email = is_duplicate_email(&conn, normalize_email(&request.email.unwrap())).await?;
+
+    let confirm_token = random_token();
+    let cancel_token = random_token();
+    let confirm_email = mailer.render(
+        user.id,
+        new_email.to_owned(),
+        user.locale.as_deref(),
+        &EmailChangeConfirmEmail {
+            username: user.username.to_owned(),
+            new_email: new_email.to_owned(),
+            link: token_link(
+                email_config.email_change_confirm_url.as_ref(),
+                &confirm_token,
+            ),
+        },
+    );
+    let notice_email = mailer.render(
+        user.id,
+        user.email,
+        user.locale.as_deref(),
+        &EmailChangeNoticeEmail {
+            username: user.username,
+            new_email: new_email.to_owned(),
+            link: token_link(email_config.email_change_cancel_url.as_ref(), &cancel_token),
+        },
+    );
+    replace_for_user(
+        &conn,
+        NewEmailChangeRequest {
+            user_id: user.id,
+            new_email: new_email.to_owned(),
+            confirm_token_hash: hash_token(&confirm_token, global_config.token_hash_key()),
+            cancel_token_hash: hash_token(&cancel_token, global_config.token_hash_key()),
+            expiry: chrono::Utc::now().naive_utc()
+                + chrono::Duration::seconds(email_config.email_verification_token_expiry),
+        },
+        confirm_email.into_iter().chain(notice_email).collect(),
+    )
+    .await?;
+
+    Ok(Status::Accepted)
+}
+
+#[post(
+    "/email/change/confirm",
+    format = "application/json",
+    data = "<request>"
+)]
+pub async fn confirm_email_change(
+    conn: DbConn,
+    request: Json<EmailChangeTokenRequest>,
+    global_config: &State<GlobalConfig>,
+) -> Result<Status, Error> {
+    let token_hash = hash_token(&request.token, global_config.token_hash_key());
+    let user_id = confirm(&conn, token_hash)
+        .await?
+        .ok_or_else(invalid_token)?;
+
+    info!("changed email for user {}", user_id);
+
+    Ok(Status::NoContent)
+}
+
+#[post(
+    "/email/change/cancel",
+    format = "application/json",
+    data = "<request>"
+)]
+pub async fn cancel_email_change(
+    conn: DbConn,
+    request: Json<EmailChangeTokenRequest>,
+    global_config: &State<GlobalConfig>,
+) -> Result<Status, Error> {
+    let token_hash = hash_token(&request.token, global_config.token_hash_key());
+
+    match delete_by_cancel_token(&conn, token_hash).await? {
+        0 => Err(invalid_token()),
+        _ => Ok(Status::NoContent),
+    }
+}
@@ -0,0 +1,78 @@
+use rocket::{get, http::Status, info, post, serde::json::Json};
+use serde::Serialize;
+
+use crate::{
+    database::DbConn,
+    models::email_outbox::{OutboxEmail, OutboxStatus},
+    repository::email_outbox::{find_by_status, retry},
+    util::{
+        authorization::AdminKey,
+        response::{Error, ErrorType},
+    },
+};
+
+const OUTBOX_PAGE_SIZE: i64 = 500;
+
+/// An outbox entry without its body, which may contain live tokens.
+#[derive(Debug, Serialize)]
+pub struct OutboxEmailResponse {
+    id: i32,
+    recipient: String,
+    subject: String,
+    status: String,
+    attempts: i32,
+    last_error: Option<String>,
+    next_attempt_at: chrono::NaiveDateTime,
+    created_at: chrono::NaiveDateTime,
+}
+
+impl OutboxEmailResponse {
+    pub fn from(email: OutboxEmail) -> Self {
+        Self {
+            id: email.id,
+            recipient: email.recipient,
+            subject: email.subject,
+            status: email.status,
+            attempts: email.attempts,
+            last_error: email.last_error,
+            next_attempt_at: email.next_attempt_at,
+            created_at: email.created_at,
+        }
+    }
+}
+
+/// Lists outbox entries by `status`, dead letters unless asked otherwise.
+#[get("/admin/email-outbox?<status>")]
+pub async fn email_outbox(
+    conn: DbConn,
+    status: Option<&str>,
+    _admin_key: AdminKey,
+) -> Result<Json<Vec<OutboxEmailResponse>>, Error> {
+    let status = match status {
+        Some(status) => OutboxStatus::parse(status).ok_or_else(|| {
+            Error::error(
+                Some((vec!["status_invalid".to_owned()], ErrorType::RequestInvalid)),
+                Status::BadRequest,
+            )
+        })?,
+        None => OutboxStatus::Dead,
+    };
+
+    let emails = find_by_status(&conn, status, OUTBOX_PAGE_SIZE).await?;
+
+    Ok(Json(
+        emails.into_iter().map(OutboxEmailResponse::from).collect(),
+    ))
+}
+
+/// Queues a dead email for delivery again.
+#[post("/admin/email-outbox/<id>/retry")]
+pub async fn retry_email(conn: DbConn, id: i32, _admin_key: AdminKey) -> Result<Status, Error> {
+    match retry(&conn, id).await? {
+        0 => Err(Error::Error(Status::NotFound)),
+        _ => {
+            info!("queued dead email {} for retry", id);
+            Ok(Status::NoContent)
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use rocket::{error, get, http::Status, serde::json::Json, State};
+
+use crate::{
+    email_sender::Mailer,
+    email_templates::RenderedEmail,
+    util::{authorization::AdminKey, locale::Locale},
+};
+
+/// Renders an email template with sample data, in `locale` or the default locale.
+#[get("/admin/email-templates/<name>/preview?<locale>")]
+pub fn preview_email_template(
+    name: &str,
+    locale: Option<&str>,
+    _admin_key: AdminKey,
+    mailer: &State<Mailer>,
+) -> Result<Json<RenderedEmail>, Status> {
+    let locale = match locale {
+        Some(locale) => Locale::parse(locale).ok_or(Status::NotFound)?,
+        None => Locale::default(),
+    };
+
+    match mailer.templates().preview(name, locale) {
+        Some(Ok(rendered)) => Ok(Json(rendered)),
+        Some(Err(e)) => {
+            error!("failed to render email template {}: {:?}", name, e);
+            Err(Status::InternalServerError)
+        }
+        None => Err(Status::NotFound),
+    }
+}
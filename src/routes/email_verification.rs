@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use rocket::{http::Status, info, post, serde::json::Json, State};
+use serde::Deserialize;
+
+use crate::{
+    database::DbConn,
+    email_sender::{token_link, LocalizedMailer, Mailer},
+    email_templates::{VerificationEmail, WelcomeEmail},
+    jwt::{EmailVerificationClaims, KeyRing},
+    models::{
+        email_outbox::NewOutboxEmail,
+        user::{EmailRequest, User},
+    },
+    repository::{
+        email_outbox,
+        user::{find_by_email, find_by_id, mark_email_verified},
+    },
+    util::{
+        globals::{EmailConfig, JWTConfig},
+        locale::Locale,
+        response::{Error, ErrorType},
+        validator::Validator,
+    },
+};
+
+#[derive(Deserialize)]
+pub struct VerifyEmailRequest {
+    token: String,
+}
+
+/// Everything needed to render a verification email, owned so it can be built inside the
+/// transaction that creates the user.
+pub struct VerificationMailer {
+    mailer: Mailer,
+    locale: Locale,
+    keyring: Arc<KeyRing>,
+    url: Option<String>,
+    expiry: i64,
+}
+
+impl VerificationMailer {
+    pub fn from(
+        mailer: &LocalizedMailer,
+        email_config: &EmailConfig,
+        jwt_config: &JWTConfig,
+    ) -> Self {
+        Self {
+            mailer: mailer.mailer().clone(),
+            locale: mailer.locale(),
+            keyring: jwt_config.keyring.clone(),
+            url: email_config.email_verification_url.to_owned(),
+            expiry: email_config.email_verification_token_expiry,
+        }
+    }
+
+    pub fn render(&self, user: &User) -> Option<NewOutboxEmail> {
+        let claims = EmailVerificationClaims::new(user, self.expiry);
+        let token = self.keyring.encode(&claims).unwrap();
+
+        self.mailer.render(
+            user.id,
+            user.email.to_owned(),
+            self.locale.or_preference(user.locale.as_deref()),
+            &VerificationEmail {
+                username: user.username.to_owned(),
+                link: token_link(self.url.as_ref(), &token),
+                expires_in_hours: self.expiry / 3600,
+            },
+        )
+    }
+}
+
+fn invalid_token() -> Error {
+    Error::error(
+        Some((vec!["token_invalid".to_owned()], ErrorType::RequestInvalid)),
+        Status::BadRequest,
+    )
+}
+
+#[post("/email/verify", format = "application/json", data = "<request>")]
+pub async fn verify_email(
+    conn: DbConn,
+    request: Json<VerifyEmailRequest>,
+    mailer: LocalizedMailer<'_>,
+    jwt_config: &State<JWTConfig>,
+) -> Result<Status, Error> {
+    let claims = jwt_config
+        .keyring
+        .decode::<EmailVerificationClaims>(&request.token)
+        .map_err(|_| invalid_token())?
+        .claims;
+    let user_id = claims.user_id().ok_or_else(invalid_token)?;
+
+    let user = find_by_id(&conn, user_id)
+        .await
+        .map_err(|_| invalid_token())?;
+
+    if user.email != claims.email {
+        return Err(invalid_token());
+    }
+
+    let welcome = mailer.render(
+        user.id,
+        user.email.to_owned(),
+        user.locale.as_deref(),
+        &WelcomeEmail {
+            username: user.username.to_owned(),
+        },
+    );
+    if mark_email_verified(&conn, user.id, claims.email, welcome.into_iter().collect()).await? == 1
+    {
+        info!("verified email for user {}", user.id);
+    }
+
+    Ok(Status::NoContent)
+}
+
+/// Sends a new verification link to an unverified address.
+#[post("/email/resend", format = "application/json", data = "<request>")]
+pub async fn resend_verification_email(
+    conn: DbConn,
+    request: Json<EmailRequest>,
+    email_config: &State<EmailConfig>,
+    mailer: LocalizedMailer<'_>,
+    jwt_config: &State<JWTConfig>,
+) -> Result<Status, Error> {
+    let request = request.into_inner();
+
+    request.validate_model()?;
+
+    if let Ok(user) = find_by_email(&conn, request.email.unwrap()).await {
+        if user.email_verified_at.is_none() {
+            let email = VerificationMailer::from(&mailer, email_config, jwt_config).render(&user);
+            email_outbox::insert(&conn, email.into_iter().collect()).await?;
+        }
+    }
+
+    Ok(Status::Accepted)
+}
@@ -0,0 +1,90 @@
+use rocket::{debug, form::Form, post, serde::json::Json, FromForm, State};
+use serde::Serialize;
+
+use crate::{
+    database::DbConn,
+    jwt::{Claims, TokenUse},
+    repository::refresh_token::find_by_token,
+    util::{
+        authorization::{validate_access_token, ServiceClient},
+        globals::{GlobalConfig, JWTConfig},
+        token_hash::hash_token,
+    },
+};
+
+#[derive(FromForm)]
+pub struct IntrospectionRequest {
+    token: String,
+}
+
+/// Tokens are not scoped, every active token grants access to the user's profile.
+const TOKEN_SCOPE: &str = "profile";
+
+#[derive(Debug, Default, Serialize)]
+pub struct IntrospectionResponse {
+    active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exp: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iat: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<&'static str>,
+    /// The OAuth token type, `token_use` tells access and refresh tokens apart.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token_type: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token_use: Option<TokenUse>,
+}
+
+impl IntrospectionResponse {
+    fn from(claims: Claims) -> Self {
+        Self {
+            active: true,
+            sub: Some(claims.sub().to_owned()),
+            exp: Some(claims.exp),
+            iat: Some(claims.iat),
+            scope: Some(TOKEN_SCOPE),
+            token_type: Some("Bearer"),
+            token_use: Some(claims.token_use),
+            username: claims.username,
+        }
+    }
+}
+
+#[post("/introspect", data = "<request>")]
+pub async fn introspect(
+    conn: DbConn,
+    service_client: ServiceClient,
+    request: Form<IntrospectionRequest>,
+    global_config: &State<GlobalConfig>,
+    jwt_config: &State<JWTConfig>,
+) -> Json<IntrospectionResponse> {
+    let ServiceClient(client_id) = service_client;
+    let token = request.into_inner().token;
+
+    debug!("introspecting token for {}", client_id);
+
+    let claims = match jwt_config.keyring.decode::<Claims>(&token) {
+        Ok(t) if t.claims.token_use == TokenUse::Access => {
+            validate_access_token(&conn, &token, jwt_config).await
+        }
+        // A rotated token is kept only to detect its reuse, presenting it to `/refresh-token`
+        // revokes the whole session.
+        Ok(t) => {
+            let token_hash = hash_token(&token, global_config.token_hash_key());
+            let now = chrono::Utc::now().naive_utc();
+            find_by_token(&conn, token_hash)
+                .await
+                .ok()
+                .filter(|stored| stored.rotated_at.is_none() && stored.expiry > now)
+                .map(|_| t.claims)
+        }
+        Err(_) => None,
+    };
+
+    Json(claims.map_or_else(IntrospectionResponse::default, IntrospectionResponse::from))
+}
@@ -0,0 +1,9 @@
+use jsonwebtoken::jwk::JwkSet;
+use rocket::{get, serde::json::Json, State};
+
+use crate::util::globals::JWTConfig;
+
+#[get("/.well-known/jwks.json")]
+pub fn jwks(jwt_config: &State<JWTConfig>) -> Json<JwkSet> {
+    Json(jwt_config.keyring.jwks())
+}
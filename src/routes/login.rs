@@ -7,17 +7,23 @@ use rocket::{
 
 use crate::{
     database::DbConn,
-    models::user::{LoginUser, UserType},
-    repository::user::find,
+    email_sender::LocalizedMailer,
+    models::{
+        security_event::{NewSecurityEvent, SecurityEventType},
+        user::LoginUser,
+    },
+    repository::user::{find, find_restorable, restore},
     util::{
-        globals::GlobalConfig,
-        response::{Error, Response, TokenResponse},
+        client_info::ClientInfo,
+        globals::{GlobalConfig, JWTConfig},
+        response::{Error, ErrorType, Response, TokenResponse},
         validator::Validator,
     },
 };
 
 use super::users_util::{
-    add_token_response, generate_and_store_refresh_token, verify_non_hashed_password,
+    generate_and_store_refresh_token, login_response, new_login_alerts, verify_non_hashed_password,
+    TokenOrigin,
 };
 
 #[post("/login", format = "application/json", data = "<user>")]
@@ -25,14 +31,23 @@ pub async fn login(
     conn: DbConn,
     user: Json<LoginUser>,
     cookies: &CookieJar<'_>,
+    client: ClientInfo,
     global_config: &State<GlobalConfig>,
+    mailer: LocalizedMailer<'_>,
+    jwt_config: &State<JWTConfig>,
 ) -> Result<Response<TokenResponse>, Error> {
     let user: LoginUser = user.into_inner();
 
     user.validate_model()?;
+    let refresh_token_in_body = user.refresh_token_in_body;
+
+    let identifier = user.identifier.clone().unwrap();
+    let found_user = match find(&conn, identifier.to_owned()).await {
+        Ok(found_user) => Ok(found_user),
+        Err(_) => find_restorable(&conn, identifier, global_config.restorable_since()).await,
+    };
 
-    let user = find(&conn, user.identifier.clone().unwrap())
-        .await
+    let user = found_user
         .map_err(|_| Error::Error(Status::Unauthorized))
         .and_then(|found_user| {
             let password_verify = verify_non_hashed_password(
@@ -47,23 +62,49 @@ pub async fn login(
             }
         })?;
 
-    generate_and_store_refresh_token(
+    // Checked before a restore, a refused login leaves a pending deletion in place.
+    if global_config.require_verified_email && user.email_verified_at.is_none() {
+        return Err(Error::error(
+            Some((
+                vec!["email_not_verified".to_owned()],
+                ErrorType::RequestInvalid,
+            )),
+            Status::Forbidden,
+        ));
+    }
+
+    // Logging in during the grace period cancels a pending account deletion.
+    let user = match user.is_deleted {
+        true => {
+            let restored_user = restore(&conn, user.id).await?;
+            crate::repository::security_event::insert(
+                &conn,
+                NewSecurityEvent::new(user.id, SecurityEventType::AccountRestored, None),
+            )
+            .await?;
+            restored_user
+        }
+        false => user,
+    };
+
+    let alerts = new_login_alerts(&conn, &user, &client, &mailer).await?;
+    let (refresh_token, session_id) = generate_and_store_refresh_token(
         &user,
-        global_config.refresh_token_expiry,
-        &global_config.auth_secret_key,
+        TokenOrigin::Login(alerts),
+        &client,
+        global_config,
+        jwt_config,
         cookies,
         &conn,
     )
     .await?;
 
-    let response = add_token_response(
-        UserType::StoredUser(&user),
-        global_config.token_expiry,
-        &global_config.auth_secret_key,
-    );
-
-    response
-        .map(|(j, s)| Ok(Response::success(Some(j), s)))
-        .or(Some(Err(Error::Error(Status::Unauthorized))))
-        .unwrap()
+    login_response(
+        &user,
+        &session_id,
+        refresh_token,
+        refresh_token_in_body,
+        global_config,
+        jwt_config,
+    )
 }
@@ -0,0 +1,51 @@
+use rocket::{
+    http::{Cookie, CookieJar, Status},
+    info, post, State,
+};
+
+use crate::{
+    database::DbConn,
+    repository::refresh_token::{delete_user_family, find_by_token},
+    util::{
+        authorization::AccessToken,
+        globals::{GlobalConfig, JWTConfig, COOKIE_REFRESH_TOKEN_NAME},
+        response::Error,
+        token_hash::hash_token,
+    },
+};
+
+use super::users_util::{get_access_token_claims, revoke_access_token};
+
+/// Ends the session of the calling access token and of the refresh cookie, if one is sent.
+#[post("/logout")]
+pub async fn logout<'a>(
+    conn: DbConn,
+    cookies: &'a CookieJar<'a>,
+    access_token: AccessToken,
+    global_config: &State<GlobalConfig>,
+    jwt_config: &State<JWTConfig>,
+) -> Result<Status, Error> {
+    let claims = get_access_token_claims(&access_token, jwt_config)?;
+    let user_id = claims.user_id().ok_or_else(Error::unauthorized)?;
+
+    let mut session_ids: Vec<String> = claims.sid.iter().cloned().collect();
+
+    if let Some(refresh_cookie) = cookies.get_private(COOKIE_REFRESH_TOKEN_NAME) {
+        let token_hash = hash_token(refresh_cookie.value(), global_config.token_hash_key());
+        if let Ok(found_token) = find_by_token(&conn, token_hash).await {
+            if !session_ids.contains(&found_token.family_id) {
+                session_ids.push(found_token.family_id);
+            }
+        }
+        cookies.remove_private(Cookie::named(COOKIE_REFRESH_TOKEN_NAME));
+    }
+
+    for session_id in session_ids {
+        delete_user_family(&conn, user_id, session_id).await?;
+    }
+
+    revoke_access_token(&conn, &claims).await?;
+    info!("logged out {}", claims.sub());
+
+    Ok(Status::NoContent)
+}
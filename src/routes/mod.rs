@@ -1,8 +1,23 @@
+pub mod account;
+pub mod account_export;
+pub mod email_change;
+pub mod email_outbox;
+pub mod email_templates;
+pub mod email_verification;
+pub mod introspect;
+pub mod jwks;
 pub mod login;
+pub mod logout;
 pub mod oauth;
 pub mod oauth_util;
+pub mod password;
 pub mod profile_lookup;
 pub mod refresh_token;
 pub mod register;
+pub mod revoke;
+pub mod sessions;
+pub mod signing_keys;
+pub mod tombstones;
+pub mod username;
 pub mod users;
 pub mod users_util;
@@ -1,11 +1,30 @@
 use super::oauth_util::{get_oauth_response, get_refresh_token};
+use super::users_util::{
+    generate_and_store_refresh_token, get_access_token_claims, login_response, new_login_alerts,
+    TokenOrigin,
+};
 use crate::{
-    oauth::twitch_authenticate,
+    database::DbConn,
+    email_sender::LocalizedMailer,
+    models::{
+        security_event::{NewSecurityEvent, SecurityEventType},
+        user::{normalize_email, NewUser, User},
+        user_identity::{NewUserIdentity, TWITCH_PROVIDER},
+    },
+    oauth::{twitch_authenticate, twitch_user, TwitchUser},
+    repository::{security_event, user, user_identity},
     util::{
-        globals::TwitchConfig,
-        response::{Error, Response, TokenResponse},
+        authorization::AccessToken,
+        client_info::ClientInfo,
+        globals::{
+            GlobalConfig, JWTConfig, TwitchConfig, COOKIE_TWITCH_REFRESH_TOKEN_NAME,
+            COOKIE_TWITCH_STATE_NAME,
+        },
+        random::random_token,
+        response::{Error, ErrorType, Response, TokenResponse},
     },
 };
+use ring::constant_time::verify_slices_are_equal;
 use rocket::{get, http::CookieJar, info, post, response::Redirect, serde::json::Json};
 use rocket::{
     http::{Cookie, Status},
@@ -23,50 +42,163 @@ pub enum GrantType {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TwitchGrant {
     code: Option<String>,
+    /// The `state` Twitch passed back to the callback along with `code`.
+    state: Option<String>,
     grant_type: GrantType,
+    /// Like for `/auth/login`, clients without cookies ask for the refresh token in the body.
+    #[serde(default)]
+    refresh_token_in_body: bool,
 }
 
+/// Seconds the sign-in has to come back from Twitch with its `state`.
+const TWITCH_STATE_EXPIRY_SECONDS: i64 = 600;
+
+/// Redirects to Twitch, remembering the `state` of the redirect in a private cookie so the
+/// code posted back can be tied to this browser.
 #[get("/oauth/twitch")]
-pub fn twitch_auth(twitch_config: &State<TwitchConfig>) -> Redirect {
+pub fn twitch_auth<'a>(
+    cookies: &'a CookieJar<'a>,
+    twitch_config: &State<TwitchConfig>,
+) -> Redirect {
     info!("redirecting to: {}", twitch_config.twitch_callback_url);
 
-    let (auth_url, _) = twitch_authenticate(
-        twitch_config.twitch_client_id.to_owned(),
-        twitch_config.twitch_client_secret.to_owned(),
-        twitch_config.twitch_callback_url.to_owned(),
+    let (auth_url, state) = twitch_authenticate(twitch_config);
+    cookies.add_private(
+        Cookie::build(COOKIE_TWITCH_STATE_NAME, state.secret().to_owned())
+            .max_age(time::Duration::seconds(TWITCH_STATE_EXPIRY_SECONDS))
+            .secure(true)
+            .http_only(true)
+            .finish(),
     );
     Redirect::to(auth_url.to_string())
 }
 
-fn handle_grant<'a>(
-    code: &str,
-    cookies: &CookieJar<'a>,
-    twitch_config: &State<TwitchConfig>,
-) -> Result<Response<TokenResponse>, Error> {
-    match get_oauth_response(
-        code.to_string(),
-        twitch_config.twitch_client_id.to_owned(),
-        twitch_config.twitch_client_secret.to_owned(),
-        twitch_config.twitch_callback_url.to_owned(),
-    ) {
-        Ok(response) => {
-            let (access_token, refresh_token, expires_in) = response;
-            cookies.add_private(Cookie::new("refresh_token", refresh_token));
-            Ok(Response::success(
-                Some(TokenResponse::success(
-                    access_token,
-                    expires_in.as_secs() as i64,
+/// Consumes the state cookie, a code is only accepted once and from the browser that
+/// started the sign-in.
+fn verify_state(cookies: &CookieJar<'_>, state: Option<&str>) -> Result<(), Error> {
+    let expected = cookies.get_private(COOKIE_TWITCH_STATE_NAME);
+    cookies.remove_private(Cookie::named(COOKIE_TWITCH_STATE_NAME));
+
+    match (expected, state) {
+        (Some(expected), Some(state))
+            if verify_slices_are_equal(expected.value().as_bytes(), state.as_bytes()).is_ok() =>
+        {
+            Ok(())
+        }
+        _ => Err(Error::Error(Status::Unauthorized)),
+    }
+}
+
+/// Runs `f` on the blocking pool, the Twitch client performs blocking requests.
+async fn with_twitch<T: Send + 'static>(
+    twitch_config: &TwitchConfig,
+    f: impl FnOnce(&TwitchConfig) -> T + Send + 'static,
+) -> Result<T, Error> {
+    let config = twitch_config.clone();
+    rocket::tokio::task::spawn_blocking(move || f(&config))
+        .await
+        .map_err(|_| Error::Error(Status::InternalServerError))
+}
+
+/// The account a Twitch user signs in as. Known identities sign in as their user, restoring
+/// it during the grace period of a deletion. An account with the same email is only linked
+/// when the request is signed in as it, otherwise a new account is created for the identity.
+async fn find_or_create_user(
+    conn: &DbConn,
+    twitch_user: TwitchUser,
+    signed_in_user_id: Option<i32>,
+    global_config: &GlobalConfig,
+) -> Result<User, Error> {
+    match user_identity::find_user(conn, TWITCH_PROVIDER, twitch_user.id.to_owned()).await? {
+        Some(user) if !user.is_deleted => return Ok(user),
+        Some(user)
+            if user
+                .deleted_at
+                .is_some_and(|deleted_at| deleted_at > global_config.restorable_since()) =>
+        {
+            let restored_user = user::restore(conn, user.id).await?;
+            security_event::insert(
+                conn,
+                NewSecurityEvent::new(user.id, SecurityEventType::AccountRestored, None),
+            )
+            .await?;
+            return Ok(restored_user);
+        }
+        Some(_) => return Err(Error::unauthorized()),
+        None => {}
+    }
+
+    let email = twitch_user
+        .email
+        .as_deref()
+        .map(normalize_email)
+        .ok_or_else(|| {
+            Error::error(
+                Some((
+                    vec!["twitch_email_unavailable".to_owned()],
+                    ErrorType::RequestInvalid,
                 )),
-                Status::Ok,
-            ))
+                Status::UnprocessableEntity,
+            )
+        })?;
+
+    match user::find_by_email(conn, email.to_owned()).await {
+        Ok(user) if signed_in_user_id == Some(user.id) => {
+            user_identity::link(
+                conn,
+                NewUserIdentity {
+                    user_id: user.id,
+                    provider: TWITCH_PROVIDER.to_owned(),
+                    provider_user_id: twitch_user.id,
+                },
+            )
+            .await?;
+            Ok(user)
         }
-        Err(e) => {
-            info!("failed to authenticated {:?}", e);
-            Err(Error::Error(Status::Unauthorized))
+        // Twitch does not prove the address belongs to whoever owns the account here, so
+        // only its signed in owner can link it.
+        Ok(_) => Err(Error::error(
+            Some((
+                vec!["twitch_link_requires_login".to_owned()],
+                ErrorType::RequestInvalid,
+            )),
+            Status::Conflict,
+        )),
+        Err(_) => {
+            let new_user = NewUser {
+                email,
+                username: twitch_user.login,
+                password: NewUser::hash_password(random_token(), &global_config.auth_secret_key),
+            };
+            user::insert_with_identity(conn, new_user, TWITCH_PROVIDER, twitch_user.id).await
         }
     }
 }
 
+/// Exchanges `code` for Twitch tokens, keeps the Twitch refresh token in a private cookie
+/// and looks up the Twitch user.
+async fn exchange_code(
+    code: String,
+    cookies: &CookieJar<'_>,
+    twitch_config: &TwitchConfig,
+) -> Result<TwitchUser, Error> {
+    let exchange = with_twitch(twitch_config, move |config| {
+        let (access_token, refresh_token, _) =
+            get_oauth_response(code, config).map_err(|e| format!("{:?}", e))?;
+        let twitch_user = twitch_user(&access_token, config).map_err(|e| format!("{:?}", e))?;
+
+        Ok::<_, String>((refresh_token, twitch_user))
+    })
+    .await?;
+    let (refresh_token, twitch_user) = exchange.map_err(|e| {
+        info!("failed to authenticated {}", e);
+        Error::Error(Status::Unauthorized)
+    })?;
+
+    cookies.add_private(Cookie::new(COOKIE_TWITCH_REFRESH_TOKEN_NAME, refresh_token));
+    Ok(twitch_user)
+}
+
 pub fn extract_refresh_token(refresh_token: Option<Cookie>) -> Result<String, Error> {
     match refresh_token {
         Some(r) => {
@@ -79,23 +211,24 @@ pub fn extract_refresh_token(refresh_token: Option<Cookie>) -> Result<String, Er
     }
 }
 
-fn handle_refresh<'a>(
-    cookies: &CookieJar<'a>,
-    twitch_config: &State<TwitchConfig>,
+/// Refreshes the Twitch access token, for clients calling the Twitch API themselves. Our
+/// own tokens are refreshed with `/auth/refresh-token`.
+async fn handle_refresh(
+    cookies: &CookieJar<'_>,
+    twitch_config: &TwitchConfig,
 ) -> Result<Response<TokenResponse>, Error> {
     info!("handling refresh token");
-    let refresh_cookie = cookies.get_private("refresh_token");
+    let refresh_cookie = cookies.get_private(COOKIE_TWITCH_REFRESH_TOKEN_NAME);
     let refresh_token = extract_refresh_token(refresh_cookie)?;
-    match get_refresh_token(
-        refresh_token,
-        twitch_config.twitch_client_id.to_owned(),
-        twitch_config.twitch_client_secret.to_owned(),
-        twitch_config.twitch_callback_url.to_owned(),
-    ) {
+    match with_twitch(twitch_config, move |config| {
+        get_refresh_token(refresh_token, config)
+    })
+    .await?
+    {
         Ok(response) => {
             info!("got refresh token response");
             let (access_token, refresh_token, expires_in) = response;
-            cookies.add_private(Cookie::new("refresh_token", refresh_token));
+            cookies.add_private(Cookie::new(COOKIE_TWITCH_REFRESH_TOKEN_NAME, refresh_token));
             Ok(Response::success(
                 Some(TokenResponse::success(
                     access_token,
@@ -108,28 +241,65 @@ fn handle_refresh<'a>(
     }
 }
 
+/// Signs in with a Twitch authorization code: the Twitch user is looked up, found or
+/// created locally, and gets our own token pair exactly like `/auth/login`. Signed in
+/// requests link the Twitch account to the signed in account with the same email.
 #[post("/oauth/twitch", format = "application/json", data = "<twitch_grant>")]
-pub fn twitch_token<'a>(
+#[allow(clippy::too_many_arguments)]
+pub async fn twitch_token(
+    conn: DbConn,
     twitch_grant: Json<TwitchGrant>,
-    cookies: &CookieJar<'a>,
+    access_token: Option<AccessToken>,
+    cookies: &CookieJar<'_>,
+    client: ClientInfo,
+    mailer: LocalizedMailer<'_>,
+    global_config: &State<GlobalConfig>,
     twitch_config: &State<TwitchConfig>,
+    jwt_config: &State<JWTConfig>,
 ) -> Result<Response<TokenResponse>, Error> {
-    let twitch_grant_inner = twitch_grant.into_inner();
-    match twitch_grant_inner.grant_type {
-        GrantType::Code if twitch_grant_inner.code.is_some() => {
-            handle_grant(&twitch_grant_inner.code.unwrap(), cookies, twitch_config)
-        }
-        GrantType::RefreshToken => handle_refresh(cookies, twitch_config),
-        _ => Err(Error::Error(Status::Unauthorized)),
-    }
+    let twitch_grant = twitch_grant.into_inner();
+    let code = match (twitch_grant.grant_type, twitch_grant.code) {
+        (GrantType::Code, Some(code)) => code,
+        (GrantType::RefreshToken, _) => return handle_refresh(cookies, twitch_config).await,
+        _ => return Err(Error::Error(Status::Unauthorized)),
+    };
+
+    verify_state(cookies, twitch_grant.state.as_deref())?;
+    let twitch_user = exchange_code(code, cookies, twitch_config).await?;
+    let signed_in_user_id = access_token
+        .and_then(|token| get_access_token_claims(&token, jwt_config).ok())
+        .and_then(|claims| claims.user_id());
+    let user = find_or_create_user(&conn, twitch_user, signed_in_user_id, global_config).await?;
+
+    let alerts = new_login_alerts(&conn, &user, &client, &mailer).await?;
+    let (refresh_token, session_id) = generate_and_store_refresh_token(
+        &user,
+        TokenOrigin::Login(alerts),
+        &client,
+        global_config,
+        jwt_config,
+        cookies,
+        &conn,
+    )
+    .await?;
+
+    login_response(
+        &user,
+        &session_id,
+        refresh_token,
+        twitch_grant.refresh_token_in_body,
+        global_config,
+        jwt_config,
+    )
 }
 
 #[get("/oauth/twitch/logout")]
-pub fn logout_twitch<'a>(
-    cookies: &CookieJar<'a>,
-) -> Status {
-    if cookies.get_private("refresh_token").is_some() {
-        cookies.remove_private(Cookie::named("refresh_token"));
+pub fn logout_twitch<'a>(cookies: &CookieJar<'a>) -> Status {
+    if cookies
+        .get_private(COOKIE_TWITCH_REFRESH_TOKEN_NAME)
+        .is_some()
+    {
+        cookies.remove_private(Cookie::named(COOKIE_TWITCH_REFRESH_TOKEN_NAME));
     }
 
     Status::Ok
@@ -1,4 +1,5 @@
 use crate::oauth::{twitch_exchange_code, twitch_refresh_access_token, ExchangeError};
+use crate::util::globals::TwitchConfig;
 use oauth2::TokenResponse;
 use rocket::{debug, info};
 use std::time::Duration;
@@ -8,11 +9,9 @@ pub type OAuthSuccessResponse = (String, RefreshToken, Duration);
 
 pub fn get_oauth_response(
     code_grant: String,
-    client_id: String,
-    client_secret: String,
-    callback_url: String,
+    config: &TwitchConfig,
 ) -> Result<OAuthSuccessResponse, ExchangeError> {
-    match twitch_exchange_code(code_grant, client_id, client_secret, callback_url) {
+    match twitch_exchange_code(code_grant, config) {
         Ok(exchange_response) => {
             info!("got exchange {:?}", exchange_response);
             let access_token = exchange_response.access_token().secret().to_owned();
@@ -31,12 +30,10 @@ pub fn get_oauth_response(
 
 pub fn get_refresh_token(
     refresh_token: String,
-    client_id: String,
-    client_secret: String,
-    callback_url: String,
+    config: &TwitchConfig,
 ) -> Result<OAuthSuccessResponse, ExchangeError> {
     debug!("got refresh token {}", refresh_token);
-    match twitch_refresh_access_token(refresh_token, client_id, client_secret, callback_url) {
+    match twitch_refresh_access_token(refresh_token, config) {
         Ok(exchange_response) => {
             debug!("got exchange refresh {:?}", exchange_response);
             let access_token = exchange_response.access_token().secret().to_owned();
@@ -0,0 +1,155 @@
+use rocket::{http::Status, info, post, serde::json::Json, State};
+
+use crate::{
+    database::DbConn,
+    email_sender::{token_link, LocalizedMailer},
+    email_templates::{PasswordChangedEmail, PasswordResetEmail},
+    models::{
+        password_reset_token::NewPasswordResetToken,
+        user::{ChangePasswordRequest, EmailRequest, NewUser, ResetPasswordRequest},
+    },
+    repository::{
+        password_reset_token::{insert, redeem},
+        user::{find_by_email, find_by_id, update_password},
+    },
+    util::{
+        authorization::AccessToken,
+        globals::{EmailConfig, GlobalConfig, JWTConfig},
+        random::random_token,
+        response::{Error, ErrorType},
+        token_hash::hash_token,
+        validator::Validator,
+    },
+};
+
+use super::users_util::{get_access_token_claims, verify_non_hashed_password};
+
+/// Emails a password reset link to a registered address.
+#[post("/password/forgot", format = "application/json", data = "<request>")]
+pub async fn forgot_password(
+    conn: DbConn,
+    request: Json<EmailRequest>,
+    global_config: &State<GlobalConfig>,
+    email_config: &State<EmailConfig>,
+    mailer: LocalizedMailer<'_>,
+) -> Result<Status, Error> {
+    let request = request.into_inner();
+
+    request.validate_model()?;
+
+    let user = match find_by_email(&conn, request.email.unwrap()).await {
+        Ok(user) => user,
+        Err(_) => return Ok(Status::Accepted),
+    };
+
+    let token = random_token();
+    let reset_email = mailer.render(
+        user.id,
+        user.email,
+        user.locale.as_deref(),
+        &PasswordResetEmail {
+            username: user.username,
+            link: token_link(email_config.password_reset_url.as_ref(), &token),
+            expires_in_minutes: email_config.password_reset_token_expiry / 60,
+        },
+    );
+    insert(
+        &conn,
+        NewPasswordResetToken {
+            token_hash: hash_token(&token, global_config.token_hash_key()),
+            user_id: user.id,
+            expiry: chrono::Utc::now().naive_utc()
+                + chrono::Duration::seconds(email_config.password_reset_token_expiry),
+        },
+        reset_email.into_iter().collect(),
+    )
+    .await?;
+
+    Ok(Status::Accepted)
+}
+
+/// Redeems a reset token, sets the new password and signs the user out everywhere.
+#[post("/password/reset", format = "application/json", data = "<request>")]
+pub async fn reset_password(
+    conn: DbConn,
+    request: Json<ResetPasswordRequest>,
+    global_config: &State<GlobalConfig>,
+) -> Result<Status, Error> {
+    let request = request.into_inner();
+
+    request.validate_model()?;
+
+    let token_hash = hash_token(&request.token.unwrap(), global_config.token_hash_key());
+    let password =
+        NewUser::hash_password(request.password.unwrap(), &global_config.auth_secret_key);
+    let (user_id, revoked) = redeem(&conn, token_hash, password).await?.ok_or_else(|| {
+        Error::error(
+            Some((vec!["token_invalid".to_owned()], ErrorType::RequestInvalid)),
+            Status::BadRequest,
+        )
+    })?;
+    info!(
+        "reset password for user {}, revoked {} refresh tokens",
+        user_id, revoked
+    );
+
+    Ok(Status::NoContent)
+}
+
+/// Sets a new password for a signed in user and signs out every other session.
+#[post("/password/change", format = "application/json", data = "<request>")]
+pub async fn change_password(
+    conn: DbConn,
+    request: Json<ChangePasswordRequest>,
+    access_token: AccessToken,
+    global_config: &State<GlobalConfig>,
+    mailer: LocalizedMailer<'_>,
+    jwt_config: &State<JWTConfig>,
+) -> Result<Status, Error> {
+    let request = request.into_inner();
+
+    request.validate_model()?;
+
+    let claims = get_access_token_claims(&access_token, jwt_config)?;
+    let user_id = claims.user_id().ok_or_else(Error::unauthorized)?;
+    let user = find_by_id(&conn, user_id).await.map_err(Error::Error)?;
+
+    if !verify_non_hashed_password(
+        &user,
+        &request.current_password.unwrap(),
+        &global_config.auth_secret_key,
+    ) {
+        return Err(Error::error(
+            Some((
+                vec!["current_password_invalid".to_owned()],
+                ErrorType::RequestInvalid,
+            )),
+            Status::Forbidden,
+        ));
+    }
+
+    let password =
+        NewUser::hash_password(request.password.unwrap(), &global_config.auth_secret_key);
+    let notice = mailer.render(
+        user.id,
+        user.email,
+        user.locale.as_deref(),
+        &PasswordChangedEmail {
+            username: user.username,
+        },
+    );
+    let revoked = update_password(
+        &conn,
+        user.id,
+        password,
+        claims.sid,
+        notice.into_iter().collect(),
+    )
+    .await?;
+    info!(
+        "changed password for user {}, revoked {} refresh tokens",
+        user.id, revoked
+    );
+
+    Ok(Status::NoContent)
+}
@@ -3,11 +3,8 @@ use rocket::{get, http::Status, serde::json::Json, State};
 use crate::{
     database::DbConn,
     models::user::User,
-    repository::user::find,
-    util::{
-        authorization::AccessToken,
-        globals::{GlobalConfig, JWTConfig},
-    },
+    repository::user::find_by_id,
+    util::{authorization::AccessToken, globals::JWTConfig},
 };
 
 use super::users_util::get_jwt_claim;
@@ -34,22 +31,17 @@ impl UserLookUpResponse {
 pub async fn profile_lookup<'a>(
     db_conn: DbConn,
     access_token: AccessToken,
-    global_config: &State<GlobalConfig>,
     jwt_config: &State<JWTConfig>,
 ) -> Result<Json<UserLookUpResponse>, Status> {
     let AccessToken(token) = access_token;
     let request_token: Vec<&str> = token.split(' ').collect();
 
-    let token_claim = get_jwt_claim(
-        request_token[1],
-        &global_config.auth_secret_key,
-        &jwt_config.validation,
-    )
-    .map_err(|_| Status::Unauthorized)?;
+    let token_claim =
+        get_jwt_claim(request_token[1], jwt_config).map_err(|_| Status::Unauthorized)?;
 
-    let identifier = token_claim.claims.sub();
+    let user_id = token_claim.claims.user_id().ok_or(Status::Unauthorized)?;
 
-    let user = find(&db_conn, identifier.to_string()).await?;
+    let user = find_by_id(&db_conn, user_id).await?;
 
     Ok(Json(UserLookUpResponse::from(user)))
 }
@@ -1,78 +1,130 @@
 use rocket::{
-    get,
-    http::{CookieJar, Status},
-    info, State,
+    http::{Cookie, CookieJar, Status},
+    info, post,
+    serde::json::Json,
+    State,
 };
+use serde::Deserialize;
 
 use crate::{
     database::DbConn,
-    models::user::UserType,
+    models::{
+        security_event::{NewSecurityEvent, SecurityEventType},
+        user::RefreshToken,
+    },
     util::{
-        authorization::AccessToken,
+        authorization::RequestOrigin,
+        client_info::ClientInfo,
         globals::{GlobalConfig, JWTConfig, COOKIE_REFRESH_TOKEN_NAME},
         response::{Error, Response, TokenResponse},
+        token_hash::hash_token,
     },
 };
 
 use super::users_util::{
-    add_token_response, generate_and_store_refresh_token, verify_jwt, verify_username,
+    add_token_response, generate_and_store_refresh_token, verify_jwt, verify_user, TokenOrigin,
 };
 
-#[get("/refresh-token")]
+/// A rotated token being presented again means it leaked, so the whole family is revoked.
+async fn revoke_reused_family(
+    conn: &DbConn,
+    cookie: &CookieJar<'_>,
+    reused_token: RefreshToken,
+) -> Error {
+    cookie.remove_private(Cookie::named(COOKIE_REFRESH_TOKEN_NAME));
+
+    let revoked =
+        crate::repository::refresh_token::delete_family(conn, reused_token.family_id.to_owned())
+            .await;
+    let recorded = crate::repository::security_event::insert(
+        conn,
+        NewSecurityEvent::new(
+            reused_token.user_id,
+            SecurityEventType::RefreshTokenReuse,
+            Some(format!("token family {}", reused_token.family_id)),
+        ),
+    )
+    .await;
+
+    match revoked.and(recorded) {
+        Ok(_) => Error::unauthorized(),
+        Err(e) => e,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RefreshTokenRequest {
+    refresh_token: String,
+}
+
+/// Browsers authenticate with the private cookie and must come from an allowed origin;
+/// other clients send the refresh token in the body and get the rotated one back in the body.
+#[post("/refresh-token", data = "<request>")]
 pub async fn refresh_token<'a>(
     conn: DbConn,
     cookie: &'a CookieJar<'a>,
-    _access_token: AccessToken,
+    origin: RequestOrigin,
+    client: ClientInfo,
+    request: Option<Json<RefreshTokenRequest>>,
     global_config: &State<GlobalConfig>,
     jwt_config: &State<JWTConfig>,
 ) -> Result<Response<TokenResponse>, Error> {
-    let refresh_token = cookie.get_private(COOKIE_REFRESH_TOKEN_NAME);
+    let is_body_token = request.is_some();
+    let token_data = match request {
+        Some(r) => r.into_inner().refresh_token,
+        None => {
+            let refresh_cookie = cookie
+                .get_private(COOKIE_REFRESH_TOKEN_NAME)
+                .ok_or(Error::error(None, Status::Unauthorized))?;
 
-    let cookie_data = refresh_token.as_ref();
+            if !origin.is_allowed(&global_config.allowed_origins) {
+                return Err(Error::Error(Status::Forbidden));
+            }
 
-    let token_data = match cookie_data {
-        None => return Err(Error::error(None, Status::Unauthorized)),
-        Some(v) => v,
+            info!("found token in cookie");
+            refresh_cookie.value().to_owned()
+        }
     };
 
-    info!("found token in cookie");
-
-    let found_token =
-        crate::repository::refresh_token::find_by_token(&conn, token_data.value().to_owned())
-            .await?;
+    let found_token = crate::repository::refresh_token::find_by_token(
+        &conn,
+        hash_token(&token_data, global_config.token_hash_key()),
+    )
+    .await?;
 
     info!("found token {:?}", found_token);
 
-    let deleted_token = crate::repository::refresh_token::delete(&conn, found_token.id).await?;
+    let is_rotated = crate::repository::refresh_token::mark_rotated(&conn, found_token.id).await?;
 
-    info!("deleted token {:?}", deleted_token);
+    if !is_rotated {
+        return Err(revoke_reused_family(&conn, cookie, found_token).await);
+    }
 
-    let verified_token = match verify_jwt(
-        token_data,
-        &global_config.auth_secret_key,
-        &jwt_config.validation,
-    ) {
+    let verified_token = match verify_jwt(&token_data, jwt_config) {
         Some(v) => v,
         None => return Err(Error::unauthorized()),
     };
 
-    let user = verify_username(&conn, verified_token).await?;
+    let user = verify_user(&conn, verified_token).await?;
 
-    generate_and_store_refresh_token(
+    let (new_refresh_token, session_id) = generate_and_store_refresh_token(
         &user,
-        global_config.refresh_token_expiry,
-        &global_config.auth_secret_key,
+        TokenOrigin::Rotation(&found_token),
+        &client,
+        global_config,
+        jwt_config,
         cookie,
         &conn,
     )
     .await?;
 
-    let token_response = add_token_response(
-        UserType::StoredUser(&user),
-        global_config.token_expiry,
-        &global_config.auth_secret_key,
-    )
-    .map(|(response, status)| Response::success(Some(response), status));
+    let token_response =
+        add_token_response(&user, &session_id, global_config.token_expiry, jwt_config)
+            .map(|(response, status)| match is_body_token {
+                true => (response.with_refresh_token(new_refresh_token), status),
+                false => (response, status),
+            })
+            .map(|(response, status)| Response::success(Some(response), status));
 
     token_response.ok_or(Error::Error(Status::Unauthorized))
 }
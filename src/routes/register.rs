@@ -3,39 +3,41 @@ use rocket::{http::Status, post, serde::json::Json, State};
 
 use crate::{
     database::DbConn,
-    email_sender::send_email,
+    email_sender::LocalizedMailer,
     models::user::{NewUser, NewUserRequest},
     repository::user::{insert, is_duplicate_user_or_email},
     util::{
-        globals::{EmailConfig, GlobalConfig},
+        globals::{EmailConfig, GlobalConfig, JWTConfig},
         response::Error,
         validator::Validator,
     },
 };
 
+use super::email_verification::VerificationMailer;
+
 #[post("/register", format = "application/json", data = "<user>")]
 pub async fn register_user(
     conn: DbConn,
     user: Json<NewUserRequest>,
     global_config: &State<GlobalConfig>,
     email_config: &State<EmailConfig>,
+    mailer: LocalizedMailer<'_>,
+    jwt_config: &State<JWTConfig>,
 ) -> Result<Status, Error> {
     let user_request = user.into_inner();
 
     user_request.validate_model()?;
 
-    is_duplicate_user_or_email(&conn, user_request)
-        .and_then(|user| insert(&conn, NewUser::from(user, &global_config.auth_secret_key)))
-        .await
-        .map(|user| {
-            if email_config.email_enabled {
-                rocket::tokio::spawn(send_email(
-                    user.email,
-                    email_config.email_username.clone(),
-                    email_config.email_password.clone(),
-                ));
-            }
+    let verification = VerificationMailer::from(&mailer, email_config, jwt_config);
 
-            Status::Created
+    is_duplicate_user_or_email(&conn, user_request)
+        .and_then(|user| {
+            insert(
+                &conn,
+                NewUser::from(user, &global_config.auth_secret_key),
+                move |user| verification.render(user).into_iter().collect(),
+            )
         })
+        .await
+        .map(|_| Status::Created)
 }
@@ -0,0 +1,49 @@
+use rocket::{form::Form, http::Status, info, post, FromForm, State};
+
+use crate::{
+    database::DbConn,
+    jwt::{Claims, TokenUse},
+    repository::refresh_token::{delete, find_by_token},
+    util::{
+        globals::{GlobalConfig, JWTConfig},
+        response::Error,
+        token_hash::hash_token,
+    },
+};
+
+use super::users_util::revoke_access_token;
+
+#[derive(FromForm)]
+pub struct RevocationRequest {
+    token: String,
+}
+
+/// Invalid, expired and unknown tokens are answered with 200 as required by RFC 7009.
+#[post("/revoke", data = "<request>")]
+pub async fn revoke(
+    conn: DbConn,
+    request: Form<RevocationRequest>,
+    global_config: &State<GlobalConfig>,
+    jwt_config: &State<JWTConfig>,
+) -> Result<Status, Error> {
+    let token = request.into_inner().token;
+
+    let claims = match jwt_config.keyring.decode::<Claims>(&token) {
+        Ok(t) => t.claims,
+        Err(_) => return Ok(Status::Ok),
+    };
+
+    match claims.token_use {
+        TokenUse::Access => revoke_access_token(&conn, &claims).await?,
+        TokenUse::Refresh => {
+            let token_hash = hash_token(&token, global_config.token_hash_key());
+            if let Ok(found_token) = find_by_token(&conn, token_hash).await {
+                delete(&conn, found_token.id).await?;
+            }
+        }
+    }
+
+    info!("revoked {:?} token for {}", claims.token_use, claims.sub());
+
+    Ok(Status::Ok)
+}
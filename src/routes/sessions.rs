@@ -0,0 +1,87 @@
+use rocket::{delete, get, http::Status, info, serde::json::Json, State};
+use serde::Serialize;
+
+use crate::{
+    database::DbConn,
+    models::user::RefreshToken,
+    repository::refresh_token::{delete_other_families, delete_user_family, find_sessions},
+    util::{authorization::AccessToken, globals::JWTConfig, response::Error},
+};
+
+use super::users_util::get_access_token_claims;
+
+#[derive(Debug, Serialize)]
+pub struct SessionResponse {
+    id: String,
+    label: String,
+    user_agent: Option<String>,
+    ip_address: Option<String>,
+    created_at: chrono::NaiveDateTime,
+    last_used_at: chrono::NaiveDateTime,
+    current: bool,
+}
+
+impl SessionResponse {
+    pub fn from(token: RefreshToken, current_session: Option<&str>) -> Self {
+        Self {
+            current: current_session == Some(token.family_id.as_str()),
+            id: token.family_id,
+            label: token.label,
+            user_agent: token.user_agent,
+            ip_address: token.ip_address,
+            created_at: token.created_at,
+            last_used_at: token.last_used_at,
+        }
+    }
+}
+
+#[get("/sessions")]
+pub async fn sessions(
+    conn: DbConn,
+    access_token: AccessToken,
+    jwt_config: &State<JWTConfig>,
+) -> Result<Json<Vec<SessionResponse>>, Error> {
+    let claims = get_access_token_claims(&access_token, jwt_config)?;
+    let user_id = claims.user_id().ok_or_else(Error::unauthorized)?;
+
+    let sessions = find_sessions(&conn, user_id).await?;
+
+    Ok(Json(
+        sessions
+            .into_iter()
+            .map(|s| SessionResponse::from(s, claims.sid.as_deref()))
+            .collect(),
+    ))
+}
+
+#[delete("/sessions/<id>")]
+pub async fn revoke_session(
+    conn: DbConn,
+    id: String,
+    access_token: AccessToken,
+    jwt_config: &State<JWTConfig>,
+) -> Result<Status, Error> {
+    let claims = get_access_token_claims(&access_token, jwt_config)?;
+    let user_id = claims.user_id().ok_or_else(Error::unauthorized)?;
+
+    match delete_user_family(&conn, user_id, id).await? {
+        0 => Err(Error::Error(Status::NotFound)),
+        _ => Ok(Status::NoContent),
+    }
+}
+
+/// Signs out every other session; the session of the calling access token is kept.
+#[delete("/sessions")]
+pub async fn revoke_other_sessions(
+    conn: DbConn,
+    access_token: AccessToken,
+    jwt_config: &State<JWTConfig>,
+) -> Result<Status, Error> {
+    let claims = get_access_token_claims(&access_token, jwt_config)?;
+    let user_id = claims.user_id().ok_or_else(Error::unauthorized)?;
+
+    let revoked = delete_other_families(&conn, user_id, claims.sid.to_owned()).await?;
+    info!("revoked {} refresh tokens for {}", revoked, claims.sub());
+
+    Ok(Status::NoContent)
+}
@@ -0,0 +1,48 @@
+use rocket::{get, http::Status, info, post, serde::json::Json, State};
+use serde::Serialize;
+
+use crate::{
+    database::DbConn,
+    repository::signing_key_promotion,
+    util::{authorization::AdminKey, globals::JWTConfig, response::Error},
+};
+
+#[derive(Debug, Serialize)]
+pub struct SigningKeysResponse {
+    active_kid: String,
+    kids: Vec<String>,
+}
+
+#[get("/admin/signing-keys")]
+pub fn signing_keys(
+    _admin_key: AdminKey,
+    jwt_config: &State<JWTConfig>,
+) -> Json<SigningKeysResponse> {
+    Json(SigningKeysResponse {
+        active_kid: jwt_config.keyring.active_kid(),
+        kids: jwt_config.keyring.kids(),
+    })
+}
+
+/// Makes `kid` the active signer. The promotion is stored so it survives restarts and is
+/// picked up by the other instances on their next key sync.
+#[post("/admin/signing-keys/<kid>/promote")]
+pub async fn promote_signing_key(
+    conn: DbConn,
+    kid: &str,
+    _admin_key: AdminKey,
+    jwt_config: &State<JWTConfig>,
+) -> Result<Status, Error> {
+    if !jwt_config.keyring.kids().iter().any(|k| k == kid) {
+        return Err(Error::Error(Status::NotFound));
+    }
+
+    signing_key_promotion::insert(&conn, kid.to_owned()).await?;
+    jwt_config
+        .keyring
+        .promote(kid)
+        .map_err(|_| Error::Error(Status::NotFound))?;
+    info!("promoted signing key {}", kid);
+
+    Ok(Status::Ok)
+}
@@ -0,0 +1,48 @@
+use rocket::{get, serde::json::Json};
+use serde::Serialize;
+
+use crate::{
+    database::DbConn,
+    models::account_tombstone::AccountTombstone,
+    repository::account_tombstone::find_after,
+    util::{authorization::ServiceClient, response::Error},
+};
+
+const TOMBSTONE_PAGE_SIZE: i64 = 500;
+
+#[derive(Debug, Serialize)]
+pub struct TombstoneResponse {
+    id: i32,
+    user_id: i32,
+    deleted_at: Option<chrono::NaiveDateTime>,
+    purged_at: chrono::NaiveDateTime,
+}
+
+impl TombstoneResponse {
+    pub fn from(tombstone: AccountTombstone) -> Self {
+        Self {
+            id: tombstone.id,
+            user_id: tombstone.user_id,
+            deleted_at: tombstone.deleted_at,
+            purged_at: tombstone.purged_at,
+        }
+    }
+}
+
+/// Purged accounts in purge order. Services page through by passing the last seen `id`
+/// as `after`.
+#[get("/tombstones?<after>")]
+pub async fn tombstones(
+    conn: DbConn,
+    _service_client: ServiceClient,
+    after: Option<i32>,
+) -> Result<Json<Vec<TombstoneResponse>>, Error> {
+    let tombstones = find_after(&conn, after.unwrap_or(0), TOMBSTONE_PAGE_SIZE).await?;
+
+    Ok(Json(
+        tombstones
+            .into_iter()
+            .map(TombstoneResponse::from)
+            .collect(),
+    ))
+}
@@ -0,0 +1,89 @@
+use rocket::{get, http::Status, info, patch, serde::json::Json, State};
+use serde::Serialize;
+
+use crate::{
+    database::DbConn,
+    models::username_history::ChangeUsernameRequest,
+    repository::{
+        user::{change_username, find_by_id, find_by_username},
+        username_history::find_previous_owner,
+    },
+    util::{
+        authorization::AccessToken,
+        globals::{GlobalConfig, JWTConfig},
+        response::Error,
+        validator::Validator,
+    },
+};
+
+use super::{profile_lookup::UserLookUpResponse, users_util::get_access_token_claims};
+
+#[derive(Debug, Serialize)]
+pub struct UsernameLookupResponse {
+    id: i32,
+    username: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    renamed_from: Option<String>,
+}
+
+/// Renames the signed in user. Access tokens issued before carry the old `username` claim
+/// until they are refreshed.
+#[patch("/profile/username", format = "application/json", data = "<request>")]
+pub async fn update_username(
+    conn: DbConn,
+    request: Json<ChangeUsernameRequest>,
+    access_token: AccessToken,
+    global_config: &State<GlobalConfig>,
+    jwt_config: &State<JWTConfig>,
+) -> Result<Json<UserLookUpResponse>, Error> {
+    let request = request.into_inner();
+
+    request.validate_model()?;
+
+    let claims = get_access_token_claims(&access_token, jwt_config)?;
+    let user_id = claims.user_id().ok_or_else(Error::unauthorized)?;
+    let user = find_by_id(&conn, user_id).await.map_err(Error::Error)?;
+    let username = request.username.unwrap();
+
+    if user.username == username {
+        return Ok(Json(UserLookUpResponse::from(user)));
+    }
+
+    let now = chrono::Utc::now().naive_utc();
+    let changed_before = now - chrono::Duration::seconds(global_config.username_change_interval);
+    let held_until = now + chrono::Duration::seconds(global_config.username_hold_period);
+    let old_username = user.username;
+    let user = change_username(&conn, user.id, username, changed_before, held_until).await?;
+    info!(
+        "renamed user {} from {} to {}",
+        user.id, old_username, user.username
+    );
+
+    Ok(Json(UserLookUpResponse::from(user)))
+}
+
+/// Resolves current and previous usernames so links to a renamed profile can redirect.
+#[get("/users/<username>")]
+pub async fn lookup_username(
+    conn: DbConn,
+    username: String,
+) -> Result<Json<UsernameLookupResponse>, Error> {
+    if let Ok(user) = find_by_username(&conn, username.to_owned()).await {
+        return Ok(Json(UsernameLookupResponse {
+            id: user.id,
+            username: user.username,
+            renamed_from: None,
+        }));
+    }
+
+    let user_id = find_previous_owner(&conn, username.to_owned())
+        .await?
+        .ok_or(Error::Error(Status::NotFound))?;
+    let user = find_by_id(&conn, user_id).await.map_err(Error::Error)?;
+
+    Ok(Json(UsernameLookupResponse {
+        id: user.id,
+        username: user.username,
+        renamed_from: Some(username),
+    }))
+}
@@ -1,27 +1,45 @@
 use crate::{
     database::DbConn,
-    jwt::{generate_header, Claims},
-    models::user::{NewRefreshToken, User, UserType},
-    util::response::{ErrorResponse, ErrorType},
+    jwt::{Claims, TokenUse},
+    models::{
+        email_outbox::NewOutboxEmail,
+        revoked_token::NewRevokedToken,
+        user::{NewRefreshToken, RefreshToken, User},
+    },
+    util::{
+        authorization::AccessToken,
+        globals::JWTConfig,
+        response::{ErrorResponse, ErrorType},
+    },
 };
 use crate::{
-    repository::user::find,
-    util::{globals::COOKIE_REFRESH_TOKEN_NAME, response::TokenResponse},
+    email_sender::LocalizedMailer,
+    email_templates::NewLoginEmail,
+    repository::{refresh_token::find_sessions, user::find_by_id},
+    util::{
+        client_info::ClientInfo,
+        globals::{GlobalConfig, COOKIE_REFRESH_TOKEN_NAME},
+        random::random_id,
+        response::{Response, TokenResponse},
+        token_hash::hash_token,
+    },
 };
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, TokenData, Validation};
+use jsonwebtoken::TokenData;
 use rocket::{
     http::{Cookie, CookieJar, Status},
     info,
 };
 use rocket_sync_db_pools::diesel::result::{DatabaseErrorInformation, Error};
 
-pub fn get_new_token(user_type: &UserType, duration: i64, secret_key: &str) -> (Claims, String) {
-    let claims = match user_type {
-        UserType::LoginUser(u) => Claims::new(&u.identifier.clone().unwrap(), duration),
-        UserType::StoredUser(u) => Claims::new(&u.username, duration),
-    };
-    let encode_key = EncodingKey::from_secret(secret_key.as_ref());
-    let new_token = encode(&generate_header(), &claims, &encode_key).unwrap();
+pub fn get_new_token(
+    user: &User,
+    duration: i64,
+    token_use: TokenUse,
+    session_id: &str,
+    jwt_config: &JWTConfig,
+) -> (Claims, String) {
+    let claims = Claims::new(user, duration, token_use, session_id);
+    let new_token = jwt_config.keyring.encode(&claims).unwrap();
     (claims, new_token)
 }
 
@@ -49,28 +67,23 @@ pub fn verify_non_hashed_password(user: &User, password: &str, secret_key: &str)
     user.verify(password, secret_key)
 }
 
-pub fn add_refresh_cookie<'a>(
-    user: UserType<'a>,
-    cookie: &CookieJar,
-    claims: &Claims,
-    refresh_token: &str,
-    exp_time: i64,
-) -> UserType<'a> {
+pub fn add_refresh_cookie(cookie: &CookieJar, claims: &Claims, refresh_token: &str, exp_time: i64) {
     let refresh_exp = get_exp_time(&claims);
     cookie.add_private(get_cookie_with_expiry_and_max_age(
         refresh_exp,
         refresh_token.to_string(),
         exp_time,
     ));
-    user
 }
 
 pub fn add_token_response(
-    user: UserType<'_>,
+    user: &User,
+    session_id: &str,
     token_expiry: i64,
-    secret_key: &str,
+    jwt_config: &JWTConfig,
 ) -> Option<(TokenResponse, Status)> {
-    let (claims, token) = get_new_token(&user, token_expiry, secret_key);
+    let (claims, token) =
+        get_new_token(user, token_expiry, TokenUse::Access, session_id, jwt_config);
     let token_exp = get_exp_time(&claims);
     Some((
         TokenResponse::success(token, token_exp.whole_seconds()),
@@ -78,28 +91,91 @@ pub fn add_token_response(
     ))
 }
 
+/// The response of a sign-in, with the refresh token in the body only when the client asks
+/// for it with `refresh_token_in_body`.
+pub fn login_response(
+    user: &User,
+    session_id: &str,
+    refresh_token: String,
+    refresh_token_in_body: bool,
+    global_config: &GlobalConfig,
+    jwt_config: &JWTConfig,
+) -> Result<Response<TokenResponse>, crate::util::response::Error> {
+    add_token_response(user, session_id, global_config.token_expiry, jwt_config)
+        .map(|(response, status)| match refresh_token_in_body {
+            true => (response.with_refresh_token(refresh_token), status),
+            false => (response, status),
+        })
+        .map(|(response, status)| Response::success(Some(response), status))
+        .ok_or(crate::util::response::Error::Error(Status::Unauthorized))
+}
+
+/// Alerts on sign-ins from a browser none of the active sessions use, but not on the first
+/// sign-in of an account. The alert is queued along with the new session.
+pub async fn new_login_alerts(
+    conn: &DbConn,
+    user: &User,
+    client: &ClientInfo,
+    mailer: &LocalizedMailer<'_>,
+) -> Result<Vec<NewOutboxEmail>, crate::util::response::Error> {
+    let sessions = find_sessions(conn, user.id).await?;
+    let new_browser =
+        !sessions.is_empty() && !sessions.iter().any(|s| s.user_agent == client.user_agent);
+    let alert = match new_browser {
+        true => mailer.render(
+            user.id,
+            user.email.to_owned(),
+            user.locale.as_deref(),
+            &NewLoginEmail {
+                username: user.username.to_owned(),
+                device: client.label(),
+                ip_address: client.ip_address.to_owned(),
+                signed_in_at: chrono::Utc::now().format("%Y-%m-%d %H:%M").to_string(),
+            },
+        ),
+        false => None,
+    };
+
+    Ok(alert.into_iter().collect())
+}
+
 pub fn get_jwt_claim<'a>(
     value: &'a str,
-    secret_key: &str,
-    validation: &Validation,
+    jwt_config: &JWTConfig,
 ) -> Result<TokenData<Claims>, jsonwebtoken::errors::Error> {
-    let decode_key = DecodingKey::from_secret(secret_key.as_ref());
-    decode::<Claims>(value, &decode_key, validation)
+    jwt_config.keyring.decode::<Claims>(value)
+}
+
+/// Claims of a token already accepted by the `AccessToken` guard.
+pub fn get_access_token_claims(
+    access_token: &AccessToken,
+    jwt_config: &JWTConfig,
+) -> Result<Claims, crate::util::response::Error> {
+    let AccessToken(token) = access_token;
+
+    token
+        .strip_prefix("Bearer ")
+        .and_then(|t| get_jwt_claim(t, jwt_config).ok())
+        .map(|t| t.claims)
+        .ok_or(crate::util::response::Error::Error(Status::Unauthorized))
 }
 
-pub fn verify_jwt(
-    cookie: &Cookie,
-    secret_key: &str,
-    validation: &Validation,
-) -> Option<TokenData<Claims>> {
-    get_jwt_claim(cookie.value(), secret_key, validation).ok()
+pub fn verify_jwt(token: &str, jwt_config: &JWTConfig) -> Option<TokenData<Claims>> {
+    get_jwt_claim(token, jwt_config)
+        .ok()
+        .filter(|t| t.claims.token_use == TokenUse::Refresh)
 }
 
-pub async fn verify_username(
+pub async fn verify_user(
     conn: &DbConn,
     token_data: TokenData<Claims>,
 ) -> Result<User, crate::util::response::Error> {
-    find(&conn, token_data.claims.sub().to_owned())
+    let user_id = token_data
+        .claims
+        .user_id()
+        .ok_or(crate::util::response::Error::Error(Status::Unauthorized))?;
+
+    find_by_id(conn, user_id)
         .await
         .map_err(|_| crate::util::response::Error::Error(Status::Unauthorized))
 }
@@ -115,6 +191,7 @@ pub fn get_database_error_response(
     let auth_response = ErrorResponse {
         error_type: Some(ErrorType::RequestInvalid),
         error_codes: Some(vec![db_error_message]),
+        messages: None,
     };
     crate::util::response::Error::error_with_body(auth_response, Status::Conflict)
 }
@@ -129,36 +206,82 @@ pub fn get_auth_error_response(error: Error) -> crate::util::response::Error {
     }
 }
 
+/// Why a refresh token is issued.
+pub enum TokenOrigin<'a> {
+    /// A login starting a new session, with the emails to queue along with it.
+    Login(Vec<NewOutboxEmail>),
+    /// The rotation of a token, continuing its session.
+    Rotation(&'a RefreshToken),
+}
+
+/// Starts a new token family (session) on login, or continues the family of the rotated
+/// token. Returns the refresh token and the session id.
 pub async fn generate_and_store_refresh_token<'a>(
     user: &User,
-    refresh_token_expiry: i64,
-    auth_secret_key: &str,
+    origin: TokenOrigin<'_>,
+    client: &ClientInfo,
+    global_config: &GlobalConfig,
+    jwt_config: &JWTConfig,
     cookie: &'a CookieJar<'a>,
     conn: &DbConn,
-) -> Result<(), crate::util::response::Error> {
+) -> Result<(String, String), crate::util::response::Error> {
+    let now = chrono::Utc::now().naive_utc();
+    let (family_id, parent_id, created_at, emails) = match origin {
+        TokenOrigin::Rotation(p) => (p.family_id.to_owned(), Some(p.id), p.created_at, vec![]),
+        TokenOrigin::Login(emails) => (random_id(), None, now, emails),
+    };
+
+    let refresh_token_expiry = global_config.refresh_token_expiry;
     let (refresh_claims, refresh_token) = get_new_token(
-        &UserType::StoredUser(user),
+        user,
         refresh_token_expiry,
-        auth_secret_key,
+        TokenUse::Refresh,
+        &family_id,
+        jwt_config,
     );
     add_refresh_cookie(
-        UserType::StoredUser(user),
         cookie,
         &refresh_claims,
         &refresh_token,
         refresh_token_expiry,
     );
 
-    let user_id = user.id;
     crate::repository::refresh_token::insert(
         conn,
         NewRefreshToken {
-            user_id,
-            token: refresh_token,
-            expiry: chrono::Utc::now().naive_utc()
-                + chrono::Duration::seconds(refresh_claims.exp as i64),
+            user_id: user.id,
+            family_id: family_id.to_owned(),
+            parent_id,
+            user_agent: client.user_agent.to_owned(),
+            ip_address: client.ip_address.to_owned(),
+            label: client.label(),
+            created_at,
+            last_used_at: now,
+            token_hash: hash_token(&refresh_token, global_config.token_hash_key()),
+            expiry: chrono::NaiveDateTime::from_timestamp(refresh_claims.exp as i64, 0),
         },
+        emails,
     )
     .await?;
+    Ok((refresh_token, family_id))
+}
+
+/// Denylist entry for the access token of `claims`, kept until the token expires.
+pub fn revoked_access_token(claims: &Claims) -> NewRevokedToken {
+    NewRevokedToken {
+        jti: claims.jti.to_owned(),
+        expiry: chrono::NaiveDateTime::from_timestamp(claims.exp as i64, 0),
+    }
+}
+
+pub async fn revoke_access_token(
+    conn: &DbConn,
+    claims: &Claims,
+) -> Result<(), crate::util::response::Error> {
+    crate::repository::revoked_token::insert(conn, revoked_access_token(claims)).await?;
+
+    crate::repository::revoked_token::delete_expired(conn)
+        .await
+        .map_err(get_auth_error_response)?;
     Ok(())
 }
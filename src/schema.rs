@@ -1,9 +1,124 @@
+table! {
+    account_exports (id) {
+        id -> Int4,
+        user_id -> Int4,
+        token_hash -> Varchar,
+        archive -> Nullable<Text>,
+        expiry -> Timestamp,
+        created_at -> Timestamp,
+        completed_at -> Nullable<Timestamp>,
+        failed_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    account_tombstones (id) {
+        id -> Int4,
+        user_id -> Int4,
+        deleted_at -> Nullable<Timestamp>,
+        purged_at -> Timestamp,
+    }
+}
+
+table! {
+    email_change_requests (id) {
+        id -> Int4,
+        user_id -> Int4,
+        new_email -> Text,
+        confirm_token_hash -> Varchar,
+        cancel_token_hash -> Varchar,
+        expiry -> Timestamp,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    email_outbox (id) {
+        id -> Int4,
+        user_id -> Int4,
+        recipient -> Varchar,
+        subject -> Text,
+        body -> Text,
+        html -> Nullable<Text>,
+        status -> Varchar,
+        attempts -> Int4,
+        last_error -> Nullable<Text>,
+        next_attempt_at -> Timestamp,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    password_reset_tokens (id) {
+        id -> Int4,
+        token_hash -> Varchar,
+        user_id -> Int4,
+        expiry -> Timestamp,
+        used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     refresh_tokens (id) {
         id -> Int4,
-        token -> Varchar,
+        token_hash -> Varchar,
+        expiry -> Timestamp,
+        user_id -> Int4,
+        family_id -> Varchar,
+        parent_id -> Nullable<Int4>,
+        rotated_at -> Nullable<Timestamp>,
+        user_agent -> Nullable<Varchar>,
+        ip_address -> Nullable<Varchar>,
+        label -> Varchar,
+        created_at -> Timestamp,
+        last_used_at -> Timestamp,
+    }
+}
+
+table! {
+    revoked_tokens (id) {
+        id -> Int4,
+        jti -> Varchar,
         expiry -> Timestamp,
+    }
+}
+
+table! {
+    security_events (id) {
+        id -> Int4,
         user_id -> Int4,
+        event_type -> Varchar,
+        detail -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    signing_key_promotions (id) {
+        id -> Int4,
+        kid -> Text,
+        promoted_at -> Timestamp,
+    }
+}
+
+table! {
+    user_identities (id) {
+        id -> Int4,
+        user_id -> Int4,
+        provider -> Text,
+        provider_user_id -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    username_history (id) {
+        id -> Int4,
+        user_id -> Int4,
+        username -> Text,
+        changed_at -> Timestamp,
+        held_until -> Timestamp,
     }
 }
 
@@ -16,9 +131,32 @@ table! {
         is_deleted -> Bool,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        email_verified_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
+        locale -> Nullable<Text>,
     }
 }
 
+joinable!(account_exports -> users (user_id));
+joinable!(email_change_requests -> users (user_id));
+joinable!(email_outbox -> users (user_id));
+joinable!(password_reset_tokens -> users (user_id));
 joinable!(refresh_tokens -> users (user_id));
+joinable!(security_events -> users (user_id));
+joinable!(user_identities -> users (user_id));
+joinable!(username_history -> users (user_id));
 
-allow_tables_to_appear_in_same_query!(refresh_tokens, users,);
+allow_tables_to_appear_in_same_query!(
+    account_exports,
+    account_tombstones,
+    email_change_requests,
+    email_outbox,
+    password_reset_tokens,
+    refresh_tokens,
+    revoked_tokens,
+    security_events,
+    signing_key_promotions,
+    user_identities,
+    username_history,
+    users,
+);
@@ -0,0 +1,97 @@
+use super::{client_with, create_user, get_access_token, get_client, login};
+use rocket::{
+    http::{ContentType, Header, Status},
+    local::blocking::Client,
+};
+use serde_json::json;
+
+fn delete_account(client: &Client, access_token: &str, password: &str) -> Status {
+    client
+        .delete("/auth/account")
+        .header(ContentType::JSON)
+        .header(Header::new("token", format!("Bearer {}", access_token)))
+        .body(json!({ "password": password }).to_string())
+        .dispatch()
+        .status()
+}
+
+fn authenticate(client: &Client, access_token: &str) -> Status {
+    client
+        .get("/auth/authenticate")
+        .header(Header::new("token", format!("Bearer {}", access_token)))
+        .dispatch()
+        .status()
+}
+
+#[test]
+fn deletes_account_and_ends_sessions() {
+    let client = get_client();
+    create_user(&client, "account_delete");
+
+    let (_, body) = login(&client, "account_delete");
+    let access_token = get_access_token(&body);
+
+    assert_eq!(
+        delete_account(&client, &access_token, "WrongPassword123"),
+        Status::Forbidden
+    );
+    assert_eq!(
+        delete_account(&client, &access_token, "Ibrahim123123"),
+        Status::NoContent
+    );
+    assert_eq!(authenticate(&client, &access_token), Status::Unauthorized);
+
+    let refresh_response = client
+        .post("/auth/refresh-token")
+        .header(Header::new("Origin", "http://localhost"))
+        .dispatch();
+    assert_eq!(refresh_response.status(), Status::Unauthorized);
+
+    let lookup_response = client.get("/auth/users/account_delete").dispatch();
+    assert_eq!(lookup_response.status(), Status::NotFound);
+}
+
+#[test]
+fn restores_deleted_account_by_logging_in() {
+    let client = get_client();
+    create_user(&client, "account_restore");
+
+    let (_, body) = login(&client, "account_restore");
+    delete_account(&client, &get_access_token(&body), "Ibrahim123123");
+
+    let (status, body) = login(&client, "account_restore");
+    assert_eq!(status, Status::Ok);
+    assert_eq!(authenticate(&client, &get_access_token(&body)), Status::Ok);
+
+    let lookup_response = client.get("/auth/users/account_restore").dispatch();
+    assert_eq!(lookup_response.status(), Status::Ok);
+}
+
+#[test]
+fn does_not_restore_account_after_grace_period() {
+    let client = client_with(json!({ "account_deletion_grace_period": 0 }));
+    create_user(&client, "account_expired");
+
+    let (_, body) = login(&client, "account_expired");
+    delete_account(&client, &get_access_token(&body), "Ibrahim123123");
+
+    assert_eq!(login(&client, "account_expired").0, Status::Unauthorized);
+}
+
+#[test]
+fn refused_unverified_login_keeps_account_deleted() {
+    let client = get_client();
+    create_user(&client, "account_unverified");
+
+    let (_, body) = login(&client, "account_unverified");
+    delete_account(&client, &get_access_token(&body), "Ibrahim123123");
+
+    let strict_client = client_with(json!({ "require_verified_email": true }));
+    assert_eq!(
+        login(&strict_client, "account_unverified").0,
+        Status::Forbidden
+    );
+
+    let lookup_response = client.get("/auth/users/account_unverified").dispatch();
+    assert_eq!(lookup_response.status(), Status::NotFound);
+}
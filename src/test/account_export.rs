@@ -0,0 +1,128 @@
+use super::{create_user, get_access_token, get_client, get_connection, get_user_id};
+use crate::{
+    schema::account_exports,
+    util::{globals::GlobalConfig, token_hash::hash_token},
+};
+use diesel::prelude::*;
+use rocket::{
+    http::{ContentType, Header, Status},
+    local::blocking::{Client, LocalResponse},
+};
+use serde_json::{json, Value};
+use std::{thread, time};
+
+fn request_export(client: &Client, access_token: &str) -> (Status, Value) {
+    let response = client
+        .post("/auth/account/export")
+        .header(Header::new("token", format!("Bearer {}", access_token)))
+        .dispatch();
+    let status = response.status();
+
+    (
+        status,
+        serde_json::from_str(&response.into_string().unwrap_or_default()).unwrap_or_default(),
+    )
+}
+
+fn download_export<'c>(client: &'c Client, token: &str) -> LocalResponse<'c> {
+    client
+        .post("/auth/account/export/download")
+        .header(ContentType::JSON)
+        .body(json!({ "token": token }).to_string())
+        .dispatch()
+}
+
+#[test]
+fn exports_account_data_through_download_token() {
+    let client = get_client();
+    create_user(&client, "account_export");
+
+    let response = client
+        .post("/auth/login")
+        .header(ContentType::JSON)
+        .body(json!({ "identifier": "account_export", "password": "Ibrahim123123" }).to_string())
+        .dispatch();
+    let access_token = get_access_token(&response.into_string());
+
+    let (status, export) = request_export(&client, &access_token);
+    assert_eq!(status, Status::Accepted);
+    let token = export["token"].as_str().unwrap().to_owned();
+
+    let mut archive = None;
+    for _ in 0..50 {
+        let response = download_export(&client, &token);
+        if response.status() == Status::Ok {
+            assert!(response
+                .headers()
+                .get_one("Content-Disposition")
+                .unwrap()
+                .starts_with("attachment"));
+            archive = response.into_string();
+            break;
+        }
+        assert_eq!(response.status(), Status::Accepted);
+        thread::sleep(time::Duration::from_millis(100));
+    }
+
+    let archive: Value = serde_json::from_str(&archive.expect("export ready")).unwrap();
+    assert_eq!(archive["account"]["username"], "account_export");
+    assert!(archive["account"].get("password").is_none());
+    assert_eq!(archive["sessions"].as_array().unwrap().len(), 1);
+    assert!(archive["security_events"].is_array());
+
+    let (status, _) = request_export(&client, &access_token);
+    assert_eq!(status, Status::Accepted);
+    assert_eq!(download_export(&client, &token).status(), Status::NotFound);
+}
+
+#[test]
+fn requires_access_token() {
+    let client = get_client();
+
+    let response = client.post("/auth/account/export").dispatch();
+
+    assert_eq!(response.status(), Status::Unauthorized);
+}
+
+#[test]
+fn reports_failed_and_interrupted_exports() {
+    let client = get_client();
+    create_user(&client, "account_export_failed");
+    let response = client
+        .post("/auth/login")
+        .header(ContentType::JSON)
+        .body(
+            json!({ "identifier": "account_export_failed", "password": "Ibrahim123123" })
+                .to_string(),
+        )
+        .dispatch();
+    let user_id = get_user_id(&get_access_token(&response.into_string()));
+
+    let now = chrono::Utc::now().naive_utc();
+    let token_hash_key = client
+        .rocket()
+        .state::<GlobalConfig>()
+        .unwrap()
+        .token_hash_key();
+    for (token, created_at, failed_at) in [
+        ("export-failed", now, Some(now)),
+        ("export-interrupted", now - chrono::Duration::hours(1), None),
+    ]
+    .iter()
+    {
+        diesel::insert_into(account_exports::table)
+            .values((
+                account_exports::user_id.eq(user_id),
+                account_exports::token_hash.eq(hash_token(token, token_hash_key)),
+                account_exports::expiry.eq(now + chrono::Duration::hours(1)),
+                account_exports::created_at.eq(created_at),
+                account_exports::failed_at.eq(failed_at),
+            ))
+            .execute(&get_connection(&client))
+            .unwrap();
+
+        let response = download_export(&client, token);
+        assert_eq!(response.status(), Status::InternalServerError);
+        assert!(response.into_string().unwrap().contains("export_failed"));
+    }
+}
@@ -0,0 +1,177 @@
+use super::{
+    client_with, create_user, get_access_token, get_client, link_token, lock_email_delivery, login,
+    memory_mail_settings, wait_for_email,
+};
+use rocket::{
+    http::{ContentType, Header, Status},
+    local::blocking::Client,
+};
+use serde_json::json;
+
+fn request_change(client: &Client, username: &str, email: &str) -> Status {
+    let (_, body) = login(client, username);
+
+    client
+        .post("/auth/email/change")
+        .header(ContentType::JSON)
+        .header(Header::new(
+            "token",
+            format!("Bearer {}", get_access_token(&body)),
+        ))
+        .body(json!({ "email": email }).to_string())
+        .dispatch()
+        .status()
+}
+
+/// The confirm token emailed to the new address and the cancel token emailed to the
+/// current one.
+fn change_tokens(client: &Client, username: &str, email: &str) -> (String, String) {
+    let confirm_email = wait_for_email(client, email, "Confirm your new email address");
+    let notice_email = wait_for_email(
+        client,
+        &format!("{}@gmail.com", username),
+        "Your email address is being changed",
+    );
+
+    (link_token(&confirm_email), link_token(&notice_email))
+}
+
+fn redeem(client: &Client, action: &str, token: &str) -> Status {
+    client
+        .post(format!("/auth/email/change/{}", action))
+        .header(ContentType::JSON)
+        .body(json!({ "token": token }).to_string())
+        .dispatch()
+        .status()
+}
+
+#[test]
+fn changes_email_once_confirmed() {
+    let _delivery = lock_email_delivery();
+    let client = client_with(memory_mail_settings());
+    create_user(&client, "email_change");
+
+    assert_eq!(
+        request_change(&client, "email_change", "email_change_new@gmail.com"),
+        Status::Accepted
+    );
+    let (confirm_token, _) = change_tokens(&client, "email_change", "email_change_new@gmail.com");
+
+    assert_eq!(
+        login(&client, "email_change_new@gmail.com").0,
+        Status::Unauthorized
+    );
+    assert_eq!(
+        redeem(&client, "confirm", &confirm_token),
+        Status::NoContent
+    );
+    assert_eq!(login(&client, "email_change_new@gmail.com").0, Status::Ok);
+    assert_eq!(
+        login(&client, "email_change@gmail.com").0,
+        Status::Unauthorized
+    );
+    assert_eq!(
+        redeem(&client, "confirm", &confirm_token),
+        Status::BadRequest
+    );
+}
+
+#[test]
+fn cancels_email_change() {
+    let _delivery = lock_email_delivery();
+    let client = client_with(memory_mail_settings());
+    create_user(&client, "email_change_cancel");
+
+    request_change(
+        &client,
+        "email_change_cancel",
+        "email_change_cancel_new@gmail.com",
+    );
+    let (confirm_token, cancel_token) = change_tokens(
+        &client,
+        "email_change_cancel",
+        "email_change_cancel_new@gmail.com",
+    );
+
+    assert_eq!(redeem(&client, "cancel", &cancel_token), Status::NoContent);
+    assert_eq!(
+        redeem(&client, "confirm", &confirm_token),
+        Status::BadRequest
+    );
+    assert_eq!(
+        login(&client, "email_change_cancel@gmail.com").0,
+        Status::Ok
+    );
+}
+
+#[test]
+fn does_not_change_to_existing_email() {
+    let client = get_client();
+    create_user(&client, "email_change_taken");
+    create_user(&client, "email_change_owner");
+
+    assert_eq!(
+        request_change(
+            &client,
+            "email_change_taken",
+            "email_change_owner@gmail.com"
+        ),
+        Status::Conflict
+    );
+}
+
+#[test]
+fn does_not_confirm_email_taken_after_request() {
+    let _delivery = lock_email_delivery();
+    let client = client_with(memory_mail_settings());
+    create_user(&client, "email_change_race");
+
+    request_change(
+        &client,
+        "email_change_race",
+        "email_change_race_new@gmail.com",
+    );
+    let (confirm_token, _) = change_tokens(
+        &client,
+        "email_change_race",
+        "email_change_race_new@gmail.com",
+    );
+    create_user(&client, "email_change_race_new");
+
+    assert_eq!(redeem(&client, "confirm", &confirm_token), Status::Conflict);
+    assert_eq!(login(&client, "email_change_race@gmail.com").0, Status::Ok);
+}
+
+#[test]
+fn does_not_confirm_email_change_of_deleted_account() {
+    let _delivery = lock_email_delivery();
+    let client = client_with(memory_mail_settings());
+    create_user(&client, "email_change_deleted");
+
+    request_change(
+        &client,
+        "email_change_deleted",
+        "email_change_deleted_new@gmail.com",
+    );
+    let (confirm_token, _) = change_tokens(
+        &client,
+        "email_change_deleted",
+        "email_change_deleted_new@gmail.com",
+    );
+    let (_, body) = login(&client, "email_change_deleted");
+    let response = client
+        .delete("/auth/account")
+        .header(ContentType::JSON)
+        .header(Header::new(
+            "token",
+            format!("Bearer {}", get_access_token(&body)),
+        ))
+        .body(json!({ "password": "Ibrahim123123" }).to_string())
+        .dispatch();
+    assert_eq!(response.status(), Status::NoContent);
+
+    assert_eq!(
+        redeem(&client, "confirm", &confirm_token),
+        Status::BadRequest
+    );
+}
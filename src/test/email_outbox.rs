@@ -0,0 +1,270 @@
+use super::{client_with, create_user, get_connection, lock_email_delivery};
+use crate::{
+    database::DbConn,
+    email_sender::{Email, EmailError, EmailTransport, Mailer},
+    jobs::deliver_emails,
+    models::email_outbox::{NewOutboxEmail, OutboxEmail},
+    repository::email_outbox::queue,
+    schema::{email_outbox, users},
+    util::{globals::OutboxConfig, outbox_cipher::OutboxCipher},
+};
+use async_trait::async_trait;
+use diesel::prelude::*;
+use rocket::{
+    http::{Header, Status},
+    local::blocking::Client,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+struct FailingTransport;
+
+#[async_trait]
+impl EmailTransport for FailingTransport {
+    async fn send(&self, _email: &Email) -> Result<(), EmailError> {
+        Err(EmailError::Io(std::io::Error::other(
+            "mail server unavailable",
+        )))
+    }
+}
+
+fn find_outbox_email(client: &Client, recipient: &str) -> OutboxEmail {
+    email_outbox::table
+        .filter(email_outbox::recipient.eq(recipient))
+        .first(&get_connection(client))
+        .unwrap()
+}
+
+fn cipher(client: &Client) -> OutboxCipher {
+    client.rocket().state::<Mailer>().unwrap().cipher()
+}
+
+fn deliver_with_failing_transport(client: &Client, max_attempts: i32) {
+    let config = OutboxConfig {
+        email_outbox_interval: 0,
+        email_outbox_batch_size: 50,
+        email_max_attempts: max_attempts,
+        email_retry_delay: 60,
+        email_dead_letter_retention: 24 * 3600,
+    };
+    let cipher = cipher(client);
+
+    rocket::tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            let conn = DbConn::get_one(client.rocket()).await.unwrap();
+            deliver_emails(&conn, Arc::new(FailingTransport), cipher, config).await
+        })
+        .unwrap();
+}
+
+/// Queues an email to `<username>@example.com` for a new user `username`, in place of its
+/// verification email. Returns the user id.
+fn queue_email(client: &Client, username: &str) -> i32 {
+    create_user(client, username);
+    let conn = get_connection(client);
+    let user_id = users::table
+        .select(users::id)
+        .filter(users::username.eq(username))
+        .first(&conn)
+        .unwrap();
+    delete_outbox_emails(client, user_id);
+
+    queue(
+        &conn,
+        vec![NewOutboxEmail {
+            user_id,
+            recipient: format!("{}@example.com", username),
+            subject: "Outbox test".to_owned(),
+            body: cipher(client).seal("Hello"),
+            html: None,
+        }],
+    )
+    .unwrap();
+    user_id
+}
+
+fn delete_outbox_emails(client: &Client, user_id: i32) {
+    diesel::delete(email_outbox::table.filter(email_outbox::user_id.eq(user_id)))
+        .execute(&get_connection(client))
+        .unwrap();
+}
+
+#[test]
+fn queues_emails_with_the_change_that_triggers_them() {
+    let _delivery = lock_email_delivery();
+    let client = client_with(json!({
+        "email_enabled": true,
+        "email_transport": "memory",
+        "email_outbox_interval": 0,
+        "admin_api_key": "admin-secret"
+    }));
+    create_user(&client, "outbox_queue");
+
+    let email = find_outbox_email(&client, "outbox_queue@gmail.com");
+    assert_eq!(email.subject, "Confirm your email address");
+    assert_eq!(email.status, "pending");
+    assert_eq!(email.attempts, 0);
+
+    diesel::delete(email_outbox::table.find(email.id))
+        .execute(&get_connection(&client))
+        .unwrap();
+}
+
+#[test]
+fn stores_email_bodies_encrypted() {
+    let _delivery = lock_email_delivery();
+    let client = client_with(json!({
+        "email_enabled": true,
+        "email_transport": "memory",
+        "email_outbox_interval": 0,
+        "email_verification_url": "http://localhost/email/verify"
+    }));
+    create_user(&client, "outbox_sealed");
+
+    let email = find_outbox_email(&client, "outbox_sealed@gmail.com");
+    let id = email.id;
+    assert!(!email.body.contains("http://localhost/email/verify"));
+    assert!(!email.html.as_ref().unwrap().contains("outbox_sealed"));
+
+    let opened = Email::open(email, &cipher(&client)).unwrap();
+    assert!(opened.body.contains("http://localhost/email/verify"));
+    assert!(opened.html.unwrap().contains("outbox_sealed"));
+
+    let email = find_outbox_email(&client, "outbox_sealed@gmail.com");
+    assert!(Email::open(email, &OutboxCipher::new("another key")).is_err());
+
+    diesel::delete(email_outbox::table.find(id))
+        .execute(&get_connection(&client))
+        .unwrap();
+}
+
+#[test]
+fn retries_failed_deliveries_with_backoff() {
+    let _delivery = lock_email_delivery();
+    let client = client_with(json!({
+        "email_enabled": true,
+        "email_transport": "memory",
+        "email_outbox_interval": 0,
+        "admin_api_key": "admin-secret"
+    }));
+    let user_id = queue_email(&client, "outbox_backoff");
+
+    deliver_with_failing_transport(&client, 3);
+    let email = find_outbox_email(&client, "outbox_backoff@example.com");
+    assert_eq!(email.status, "pending");
+    assert_eq!(email.attempts, 1);
+    assert!(email
+        .last_error
+        .unwrap()
+        .contains("mail server unavailable"));
+    let delay = email.next_attempt_at - chrono::Utc::now().naive_utc();
+    assert!(delay > chrono::Duration::seconds(50));
+    assert!(delay <= chrono::Duration::seconds(60));
+
+    delete_outbox_emails(&client, user_id);
+}
+
+#[test]
+fn dead_letters_can_be_listed_and_retried() {
+    let _delivery = lock_email_delivery();
+    let client = client_with(json!({
+        "email_enabled": true,
+        "email_transport": "memory",
+        "email_outbox_interval": 0,
+        "admin_api_key": "admin-secret"
+    }));
+    let user_id = queue_email(&client, "outbox_dead");
+
+    deliver_with_failing_transport(&client, 1);
+    let email = find_outbox_email(&client, "outbox_dead@example.com");
+    assert_eq!(email.status, "dead");
+    assert_eq!(email.attempts, 1);
+
+    let response = client
+        .get("/auth/admin/email-outbox")
+        .header(Header::new("admin-key", "admin-secret"))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let listed: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    let listed = listed
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|e| e["id"] == email.id)
+        .unwrap();
+    assert_eq!(listed["recipient"], "outbox_dead@example.com");
+    assert!(listed.get("body").is_none());
+
+    let response = client
+        .post(format!("/auth/admin/email-outbox/{}/retry", email.id))
+        .header(Header::new("admin-key", "admin-secret"))
+        .dispatch();
+    assert_eq!(response.status(), Status::NoContent);
+
+    let email = find_outbox_email(&client, "outbox_dead@example.com");
+    assert_eq!(email.status, "pending");
+    assert_eq!(email.attempts, 0);
+
+    // Only dead emails can be retried.
+    let response = client
+        .post(format!("/auth/admin/email-outbox/{}/retry", email.id))
+        .header(Header::new("admin-key", "admin-secret"))
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+
+    delete_outbox_emails(&client, user_id);
+}
+
+#[test]
+fn deletes_dead_letters_after_retention() {
+    let _delivery = lock_email_delivery();
+    let client = client_with(json!({
+        "email_enabled": true,
+        "email_transport": "memory",
+        "email_outbox_interval": 0
+    }));
+    let user_id = queue_email(&client, "outbox_expired");
+
+    deliver_with_failing_transport(&client, 1);
+    let email = find_outbox_email(&client, "outbox_expired@example.com");
+    assert_eq!(email.status, "dead");
+
+    // Still within the day of retention.
+    deliver_with_failing_transport(&client, 1);
+    find_outbox_email(&client, "outbox_expired@example.com");
+
+    diesel::update(email_outbox::table.find(email.id))
+        .set(
+            email_outbox::next_attempt_at
+                .eq(chrono::Utc::now().naive_utc() - chrono::Duration::days(2)),
+        )
+        .execute(&get_connection(&client))
+        .unwrap();
+    deliver_with_failing_transport(&client, 1);
+
+    let remaining = email_outbox::table
+        .filter(email_outbox::user_id.eq(user_id))
+        .count()
+        .get_result::<i64>(&get_connection(&client));
+    assert_eq!(remaining, Ok(0));
+}
+
+#[test]
+fn rejects_unknown_outbox_status() {
+    let client = client_with(json!({
+        "email_enabled": true,
+        "email_transport": "memory",
+        "email_outbox_interval": 0,
+        "admin_api_key": "admin-secret"
+    }));
+
+    let response = client
+        .get("/auth/admin/email-outbox?status=sent")
+        .header(Header::new("admin-key", "admin-secret"))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::BadRequest);
+}
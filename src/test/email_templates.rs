@@ -0,0 +1,71 @@
+use super::client_with;
+use rocket::{
+    http::{Header, Status},
+    local::blocking::Client,
+};
+use serde_json::{json, Value};
+
+fn preview(client: &Client, name: &str) -> (Status, Option<Value>) {
+    let response = client
+        .get(format!("/auth/admin/email-templates/{}/preview", name))
+        .header(Header::new("admin-key", "admin-secret"))
+        .dispatch();
+
+    (
+        response.status(),
+        response
+            .into_string()
+            .and_then(|body| serde_json::from_str(&body).ok()),
+    )
+}
+
+#[test]
+fn previews_templates_with_sample_data() {
+    let client = client_with(json!({
+        "admin_api_key": "admin-secret",
+        "email_template_dir": "templates/email"
+    }));
+
+    let (status, rendered) = preview(&client, "password_reset");
+    let rendered = rendered.unwrap();
+
+    assert_eq!(status, Status::Ok);
+    assert_eq!(rendered["subject"], "Reset your password");
+    assert!(rendered["text"]
+        .as_str()
+        .unwrap()
+        .contains("https://example.com/reset?token=sample"));
+    assert!(rendered["html"]
+        .as_str()
+        .unwrap()
+        .starts_with("<p>Hi ibrahim,</p>"));
+
+    assert_eq!(preview(&client, "unknown").0, Status::NotFound);
+
+    let response = client
+        .get("/auth/admin/email-templates/welcome/preview")
+        .dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+}
+
+#[test]
+fn overrides_builtin_templates_from_directory() {
+    let dir = std::env::temp_dir().join(format!("templates-{}", crate::util::random::random_id()));
+    std::fs::create_dir_all(dir.join("welcome")).unwrap();
+    std::fs::write(dir.join("welcome/subject.txt"), "Hello {{ username }}!").unwrap();
+    let client = client_with(json!({
+        "admin_api_key": "admin-secret",
+        "email_template_dir": dir.to_str().unwrap()
+    }));
+
+    let (_, welcome) = preview(&client, "welcome");
+    let (_, verification) = preview(&client, "email_verification");
+
+    assert_eq!(welcome.unwrap()["subject"], "Hello ibrahim!");
+    assert_eq!(
+        verification.unwrap()["subject"],
+        "Confirm your email address"
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,129 @@
+use super::{
+    client_with, create_user, link_token, lock_email_delivery, memory_mail_settings, wait_for_email,
+};
+use crate::{email_sender::Mailer, util::globals::EmailConfig};
+use rocket::http::{ContentType, Header, Status};
+use serde_json::json;
+use std::{thread, time};
+
+#[test]
+fn defaults_smtp_port_to_tls_mode() {
+    for (tls, port) in [("starttls", 587), ("tls", 465), ("none", 25)].iter() {
+        let figment = rocket::Config::figment().merge(("email_smtp_tls", tls));
+        assert_eq!(figment.extract::<EmailConfig>().unwrap().smtp_port(), *port);
+
+        let figment = figment.merge(("email_smtp_port", 2525));
+        assert_eq!(figment.extract::<EmailConfig>().unwrap().smtp_port(), 2525);
+    }
+}
+
+#[test]
+fn delivers_emails_to_memory_transport() {
+    let _delivery = lock_email_delivery();
+    let mut settings = memory_mail_settings();
+    settings["email_from"] = json!("Auth <auth@example.com>");
+    let client = client_with(settings);
+    create_user(&client, "memory_mail");
+
+    wait_for_email(
+        &client,
+        "memory_mail@gmail.com",
+        "Confirm your email address",
+    );
+
+    let response = client
+        .post("/auth/password/forgot")
+        .header(ContentType::JSON)
+        .body(json!({ "email": "memory_mail@gmail.com" }).to_string())
+        .dispatch();
+    assert_eq!(response.status(), Status::Accepted);
+
+    let email = wait_for_email(&client, "memory_mail@gmail.com", "Reset your password");
+    assert!(email.html.as_ref().unwrap().contains("Reset your password"));
+    let token = link_token(&email);
+
+    let response = client
+        .post("/auth/password/reset")
+        .header(ContentType::JSON)
+        .body(
+            json!({
+                "token": token,
+                "password": "NewPassword123",
+                "password_repeat": "NewPassword123"
+            })
+            .to_string(),
+        )
+        .dispatch();
+    assert_eq!(response.status(), Status::NoContent);
+}
+
+#[test]
+fn writes_emails_to_file_transport() {
+    let _delivery = lock_email_delivery();
+    let dir = std::env::temp_dir().join(format!("emails-{}", crate::util::random::random_id()));
+    let client = client_with(json!({
+        "email_enabled": true,
+        "email_outbox_interval": 1,
+        "email_transport": "file",
+        "email_file_dir": dir.to_str().unwrap(),
+        "email_from": "auth@example.com"
+    }));
+    create_user(&client, "file_mail");
+
+    let mut message = None;
+    for _ in 0..50 {
+        message = std::fs::read_dir(&dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|e| std::fs::read_to_string(e.ok()?.path()).ok())
+            .find(|m| m.contains("To: file_mail@gmail.com"));
+        if message.is_some() {
+            break;
+        }
+        thread::sleep(time::Duration::from_millis(100));
+    }
+
+    assert!(message
+        .unwrap()
+        .contains("Subject: Confirm your email address"));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn alerts_on_sign_in_from_new_browser() {
+    let _delivery = lock_email_delivery();
+    let client = client_with(memory_mail_settings());
+    create_user(&client, "login_alert");
+
+    for user_agent in &[
+        "Mozilla/5.0 (X11; Linux x86_64) Firefox/90.0",
+        "Mozilla/5.0 (Windows NT 10.0) Chrome/91.0",
+    ] {
+        let response = client
+            .post("/auth/login")
+            .header(ContentType::JSON)
+            .header(Header::new("User-Agent", *user_agent))
+            .body(json!({ "identifier": "login_alert", "password": "Ibrahim123123" }).to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    let email = wait_for_email(
+        &client,
+        "login_alert@gmail.com",
+        "New sign-in to your account",
+    );
+    assert!(email.body.contains("Chrome on Windows"));
+    assert_eq!(
+        client
+            .rocket()
+            .state::<Mailer>()
+            .unwrap()
+            .sent()
+            .iter()
+            .filter(|e| e.subject == "New sign-in to your account")
+            .count(),
+        1
+    );
+}
@@ -0,0 +1,118 @@
+use super::{
+    client_with, create_user, get_access_token, get_client, get_connection, get_token_claims,
+    link_token, lock_email_delivery, login, memory_mail_settings, wait_for_email,
+};
+use crate::schema::users;
+use diesel::prelude::*;
+use rocket::{
+    http::{ContentType, Status},
+    local::blocking::Client,
+};
+use serde_json::json;
+
+fn get_verification_token(client: &Client, username: &str) -> String {
+    link_token(&wait_for_email(
+        client,
+        &format!("{}@gmail.com", username),
+        "Confirm your email address",
+    ))
+}
+
+fn verify_email(client: &Client, token: &str) -> Status {
+    client
+        .post("/auth/email/verify")
+        .header(ContentType::JSON)
+        .body(json!({ "token": token }).to_string())
+        .dispatch()
+        .status()
+}
+
+#[test]
+fn verifies_email_with_emailed_token() {
+    let _delivery = lock_email_delivery();
+    let client = client_with(memory_mail_settings());
+    create_user(&client, "verify_email");
+
+    let (_, body) = login(&client, "verify_email");
+    assert_eq!(
+        get_token_claims(&get_access_token(&body))["email_verified"],
+        false
+    );
+
+    let token = get_verification_token(&client, "verify_email");
+
+    assert_eq!(verify_email(&client, &token), Status::NoContent);
+
+    let (_, body) = login(&client, "verify_email");
+    assert_eq!(
+        get_token_claims(&get_access_token(&body))["email_verified"],
+        true
+    );
+}
+
+#[test]
+fn does_not_verify_with_invalid_token() {
+    let client = get_client();
+    create_user(&client, "verify_email_invalid");
+
+    let (_, body) = login(&client, "verify_email_invalid");
+
+    assert_eq!(verify_email(&client, "invalid"), Status::BadRequest);
+    assert_eq!(
+        verify_email(&client, &get_access_token(&body)),
+        Status::BadRequest
+    );
+}
+
+#[test]
+fn does_not_verify_email_that_changed() {
+    let _delivery = lock_email_delivery();
+    let client = client_with(memory_mail_settings());
+    create_user(&client, "verify_email_changed");
+
+    let token = get_verification_token(&client, "verify_email_changed");
+    diesel::update(users::table.filter(users::username.eq("verify_email_changed")))
+        .set(users::email.eq("verify_email_changed_new@gmail.com"))
+        .execute(&get_connection(&client))
+        .unwrap();
+
+    assert_eq!(verify_email(&client, &token), Status::BadRequest);
+}
+
+#[test]
+fn accepts_resend_for_any_email() {
+    let client = get_client();
+    create_user(&client, "verify_email_resend");
+
+    for email in &[
+        "verify_email_resend@gmail.com",
+        "verify_email_unknown@gmail.com",
+    ] {
+        let response = client
+            .post("/auth/email/resend")
+            .header(ContentType::JSON)
+            .body(json!({ "email": email }).to_string())
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Accepted);
+    }
+}
+
+#[test]
+fn refuses_login_until_verified_when_required() {
+    let _delivery = lock_email_delivery();
+    let mut settings = memory_mail_settings();
+    settings["require_verified_email"] = json!(true);
+    let client = client_with(settings);
+    create_user(&client, "verify_email_required");
+
+    let (status, body) = login(&client, "verify_email_required");
+    assert_eq!(status, Status::Forbidden);
+    assert!(body.unwrap().contains("email_not_verified"));
+
+    let token = get_verification_token(&client, "verify_email_required");
+    assert_eq!(verify_email(&client, &token), Status::NoContent);
+
+    let (status, _) = login(&client, "verify_email_required");
+    assert_eq!(status, Status::Ok);
+}
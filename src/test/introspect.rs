@@ -0,0 +1,107 @@
+use super::{basic_auth, client_with, create_user, get_access_token};
+use rocket::{
+    http::{ContentType, Header, Status},
+    local::blocking::Client,
+};
+use serde_json::{json, Value};
+
+fn introspect(client: &Client, token: &str) -> Value {
+    let response = client
+        .post("/auth/introspect")
+        .header(ContentType::Form)
+        .header(basic_auth("chat", "chat-secret"))
+        .body(format!("token={}&token_type_hint=access_token", token))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+
+    serde_json::from_str(&response.into_string().unwrap()).unwrap()
+}
+
+#[test]
+fn introspects_access_and_refresh_tokens() {
+    let client = client_with(json!({
+        "service_clients": [{ "client_id": "chat", "client_secret": "chat-secret" }]
+    }));
+    create_user(&client, "introspect");
+
+    let response = client
+        .post("/auth/login")
+        .header(ContentType::JSON)
+        .body(r#"{ "identifier": "introspect", "password": "Ibrahim123123" }"#)
+        .dispatch();
+    let access_token = get_access_token(&response.into_string());
+    let refresh_token = client.cookies().get_private("refresh_token").unwrap();
+
+    let access = introspect(&client, &access_token);
+    assert_eq!(access["active"], true);
+    assert_eq!(access["token_type"], "Bearer");
+    assert_eq!(access["token_use"], "access");
+    assert_eq!(access["scope"], "profile");
+    assert_eq!(access["username"], "introspect");
+
+    let refresh = introspect(&client, refresh_token.value());
+    assert_eq!(refresh["active"], true);
+    assert_eq!(refresh["token_type"], "Bearer");
+    assert_eq!(refresh["token_use"], "refresh");
+    assert_eq!(refresh["sub"], access["sub"]);
+}
+
+#[test]
+fn reports_rotated_refresh_token_as_inactive() {
+    let client = client_with(json!({
+        "service_clients": [{ "client_id": "chat", "client_secret": "chat-secret" }]
+    }));
+    create_user(&client, "introspect_rotated");
+    client
+        .post("/auth/login")
+        .header(ContentType::JSON)
+        .body(r#"{ "identifier": "introspect_rotated", "password": "Ibrahim123123" }"#)
+        .dispatch();
+    let rotated_token = client
+        .cookies()
+        .get_private("refresh_token")
+        .unwrap()
+        .value()
+        .to_owned();
+
+    let response = client
+        .post("/auth/refresh-token")
+        .header(Header::new("Origin", "http://localhost"))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let current_token = client.cookies().get_private("refresh_token").unwrap();
+
+    assert_eq!(
+        introspect(&client, &rotated_token),
+        json!({ "active": false })
+    );
+    assert_eq!(introspect(&client, current_token.value())["active"], true);
+}
+
+#[test]
+fn reports_invalid_token_as_inactive() {
+    let client = client_with(json!({
+        "service_clients": [{ "client_id": "chat", "client_secret": "chat-secret" }]
+    }));
+
+    let response = introspect(&client, "not-a-token");
+
+    assert_eq!(response, json!({ "active": false }));
+}
+
+#[test]
+fn does_not_introspect_without_client_credentials() {
+    let client = client_with(json!({
+        "service_clients": [{ "client_id": "chat", "client_secret": "chat-secret" }]
+    }));
+
+    let response = client
+        .post("/auth/introspect")
+        .header(ContentType::Form)
+        .header(basic_auth("chat", "wrong-secret"))
+        .body("token=not-a-token")
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Unauthorized);
+}
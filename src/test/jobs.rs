@@ -0,0 +1,182 @@
+use super::{
+    basic_auth, client_with, create_user, get_access_token, get_connection, get_user_id, login,
+};
+use crate::{
+    jobs::{purge_deleted_accounts, purge_tokens},
+    models::email_outbox::NewOutboxEmail,
+    repository::email_outbox::queue,
+    schema::{email_outbox, refresh_tokens, users},
+    util::globals::{CleanupConfig, OutboxConfig},
+};
+use diesel::{pg::PgConnection, prelude::*};
+use rocket::http::{ContentType, Header, Status};
+use serde_json::{json, Value};
+use std::{thread, time};
+
+fn count_refresh_tokens(conn: &PgConnection, user_id: i32) -> i64 {
+    refresh_tokens::table
+        .filter(refresh_tokens::user_id.eq(user_id))
+        .count()
+        .get_result(conn)
+        .unwrap()
+}
+
+#[test]
+fn rejects_batch_sizes_below_one() {
+    for batch_size in &[0, -1] {
+        let figment = rocket::Config::figment()
+            .merge(("token_cleanup_batch_size", batch_size))
+            .merge(("account_purge_batch_size", batch_size))
+            .merge(("email_outbox_batch_size", batch_size));
+
+        assert!(figment.extract::<CleanupConfig>().is_err());
+        assert!(figment.extract::<OutboxConfig>().is_err());
+    }
+}
+
+#[test]
+fn stores_refresh_token_expiry_from_refresh_token_lifetime() {
+    let client = client_with(json!({ "token_cleanup_interval": 0 }));
+    create_user(&client, "cleanup_expiry");
+    let user_id = get_user_id(&get_access_token(&login(&client, "cleanup_expiry").1));
+
+    let expiry: chrono::NaiveDateTime = refresh_tokens::table
+        .select(refresh_tokens::expiry)
+        .filter(refresh_tokens::user_id.eq(user_id))
+        .first(&get_connection(&client))
+        .unwrap();
+    let lifetime = expiry - chrono::Utc::now().naive_utc();
+
+    assert!(lifetime <= chrono::Duration::seconds(3600));
+    assert!(lifetime > chrono::Duration::seconds(3500));
+}
+
+#[test]
+fn purges_expired_refresh_tokens_in_batches() {
+    let client = client_with(json!({
+        "refresh_token_expiry": 1,
+        "token_cleanup_interval": 0
+    }));
+    create_user(&client, "cleanup_expired");
+    login(&client, "cleanup_expired");
+    let user_id = get_user_id(&get_access_token(&login(&client, "cleanup_expired").1));
+    let conn = get_connection(&client);
+
+    assert_eq!(count_refresh_tokens(&conn, user_id), 2);
+
+    thread::sleep(time::Duration::from_millis(2000));
+    let report = purge_tokens(&conn, 1).unwrap();
+
+    assert!(report.expired_refresh_tokens >= 2);
+    assert_eq!(count_refresh_tokens(&conn, user_id), 0);
+}
+
+#[test]
+fn purges_rotated_tokens_of_revoked_sessions() {
+    let client = client_with(json!({ "token_cleanup_interval": 0 }));
+    create_user(&client, "cleanup_revoked");
+    let user_id = get_user_id(&get_access_token(&login(&client, "cleanup_revoked").1));
+
+    let response = client
+        .post("/auth/refresh-token")
+        .header(Header::new("Origin", "http://localhost"))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let current_token = client.cookies().get_private("refresh_token").unwrap();
+    client
+        .post("/auth/revoke")
+        .header(ContentType::Form)
+        .body(format!("token={}", current_token.value()))
+        .dispatch();
+
+    let conn = get_connection(&client);
+    diesel::update(refresh_tokens::table.filter(refresh_tokens::user_id.eq(user_id)))
+        .set(
+            refresh_tokens::rotated_at
+                .eq(chrono::Utc::now().naive_utc() - chrono::Duration::hours(1)),
+        )
+        .execute(&conn)
+        .unwrap();
+    assert_eq!(count_refresh_tokens(&conn, user_id), 1);
+
+    let report = purge_tokens(&conn, 100).unwrap();
+
+    assert!(report.revoked_refresh_tokens >= 1);
+    assert_eq!(count_refresh_tokens(&conn, user_id), 0);
+}
+
+#[test]
+fn purges_accounts_deleted_before_retention_period() {
+    let client = client_with(json!({
+        "token_cleanup_interval": 0,
+        "account_purge_interval": 0,
+        "service_clients": [{ "client_id": "chat", "client_secret": "chat-secret" }]
+    }));
+    create_user(&client, "purge_deleted");
+    let access_token = get_access_token(&login(&client, "purge_deleted").1);
+    let user_id = get_user_id(&access_token);
+
+    let response = client
+        .delete("/auth/account")
+        .header(ContentType::JSON)
+        .header(Header::new("token", format!("Bearer {}", access_token)))
+        .body(json!({ "password": "Ibrahim123123" }).to_string())
+        .dispatch();
+    assert_eq!(response.status(), Status::NoContent);
+
+    let conn = get_connection(&client);
+    queue(
+        &conn,
+        vec![NewOutboxEmail {
+            user_id,
+            recipient: "purge_deleted@gmail.com".to_owned(),
+            subject: "Purge test".to_owned(),
+            body: "Hello".to_owned(),
+            html: None,
+        }],
+    )
+    .unwrap();
+    let retention_period = 24 * 3600;
+    purge_deleted_accounts(&conn, retention_period, 100).unwrap();
+    assert_eq!(
+        users::table.find(user_id).count().get_result::<i64>(&conn),
+        Ok(1)
+    );
+
+    diesel::update(users::table.find(user_id))
+        .set(users::deleted_at.eq(chrono::Utc::now().naive_utc() - chrono::Duration::days(2)))
+        .execute(&conn)
+        .unwrap();
+    assert!(purge_deleted_accounts(&conn, retention_period, 100).unwrap() >= 1);
+    assert_eq!(
+        users::table.find(user_id).count().get_result::<i64>(&conn),
+        Ok(0)
+    );
+    assert_eq!(count_refresh_tokens(&conn, user_id), 0);
+    assert_eq!(
+        email_outbox::table
+            .filter(email_outbox::user_id.eq(user_id))
+            .count()
+            .get_result::<i64>(&conn),
+        Ok(0)
+    );
+
+    let response = client
+        .get("/auth/tombstones")
+        .header(basic_auth("chat", "chat-secret"))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let tombstones: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    let tombstone = tombstones
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|t| t["user_id"] == user_id)
+        .unwrap();
+    assert!(tombstone["purged_at"].is_string());
+
+    let response = client.get("/auth/tombstones").dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+}
@@ -0,0 +1,46 @@
+use super::{client_with, create_user, get_access_token, get_client, write_ed25519_key};
+use jsonwebtoken::{decode, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use rocket::http::{ContentType, Status};
+use serde_json::{json, Value};
+
+#[test]
+fn does_not_publish_shared_secret() {
+    let client = get_client();
+
+    let response = client.get("/auth/.well-known/jwks.json").dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+
+    let jwks: JwkSet = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert!(jwks.keys.is_empty());
+}
+
+#[test]
+fn publishes_public_key_that_verifies_access_token() {
+    let client = client_with(json!({
+        "jwt_algorithm": "EdDSA",
+        "jwt_private_key_file": write_ed25519_key("jwks")
+    }));
+    create_user(&client, "jwks_eddsa");
+
+    let token_response = client
+        .post("/auth/login")
+        .header(ContentType::JSON)
+        .body(r#"{ "identifier": "jwks_eddsa", "password": "Ibrahim123123" }"#)
+        .dispatch();
+
+    let access_token = get_access_token(&token_response.into_string());
+
+    let response = client.get("/auth/.well-known/jwks.json").dispatch();
+    let jwks: JwkSet = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+
+    assert_eq!(jwks.keys.len(), 1);
+
+    let decoding_key = DecodingKey::from_jwk(&jwks.keys[0]).unwrap();
+    let mut validation = Validation::new(Algorithm::EdDSA);
+    validation.validate_nbf = false;
+
+    let token = decode::<Value>(&access_token, &decoding_key, &validation);
+
+    assert!(token.is_ok());
+}
@@ -0,0 +1,158 @@
+use super::{
+    client_with, get_access_token, get_client, get_connection, lock_email_delivery, login,
+};
+use crate::{models::email_outbox::OutboxEmail, schema::email_outbox, util::locale::Locale};
+use diesel::prelude::*;
+use rocket::{
+    http::{ContentType, Header, Status},
+    local::blocking::Client,
+};
+use serde_json::{json, Value};
+
+fn update_locale(client: &Client, access_token: &str, locale: Value) -> (Status, Option<String>) {
+    let response = client
+        .put("/auth/account/locale")
+        .header(ContentType::JSON)
+        .header(Header::new("token", format!("Bearer {}", access_token)))
+        .body(json!({ "locale": locale }).to_string())
+        .dispatch();
+
+    (response.status(), response.into_string())
+}
+
+fn take_outbox_email(client: &Client, recipient: &str) -> OutboxEmail {
+    let conn = get_connection(client);
+    let email: OutboxEmail = email_outbox::table
+        .filter(email_outbox::recipient.eq(recipient))
+        .order(email_outbox::id.desc())
+        .first(&conn)
+        .unwrap();
+    diesel::delete(email_outbox::table.filter(email_outbox::recipient.eq(recipient)))
+        .execute(&conn)
+        .unwrap();
+
+    email
+}
+
+#[test]
+fn negotiates_locale_from_accept_language() {
+    assert_eq!(
+        Locale::negotiate(Some("de-AT,de;q=0.9,en;q=0.8")).as_str(),
+        "de"
+    );
+    assert_eq!(Locale::negotiate(Some("fr-CA, en;q=0.5")).as_str(), "en");
+    assert_eq!(Locale::negotiate(Some("fr-CA")).as_str(), "en");
+    assert_eq!(Locale::negotiate(None).as_str(), "en");
+}
+
+#[test]
+fn localizes_error_messages() {
+    let client = get_client();
+    let json = json!({
+        "username": "locale_errors",
+        "email": "locale_errors@gmail.com",
+        "password": "short",
+        "password_repeat": "short"
+    });
+
+    let response = client
+        .post("/auth/register")
+        .header(ContentType::JSON)
+        .header(Header::new("Accept-Language", "de-DE,de;q=0.9"))
+        .body(json.to_string())
+        .dispatch();
+    let body: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert_eq!(body["error_codes"], json!(["password_length_invalid"]));
+    assert_eq!(
+        body["messages"],
+        json!(["Passwörter brauchen mindestens 12 Zeichen."])
+    );
+
+    let response = client
+        .post("/auth/register")
+        .header(ContentType::JSON)
+        .body(json.to_string())
+        .dispatch();
+    let body: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert_eq!(
+        body["messages"],
+        json!(["Passwords need at least 12 characters."])
+    );
+}
+
+#[test]
+fn previews_templates_in_locale() {
+    let client = client_with(json!({ "admin_api_key": "admin-secret" }));
+
+    let response = client
+        .get("/auth/admin/email-templates/password_reset/preview?locale=de")
+        .header(Header::new("admin-key", "admin-secret"))
+        .dispatch();
+    let rendered: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert_eq!(rendered["subject"], "Setze dein Passwort zurück");
+    assert!(rendered["text"]
+        .as_str()
+        .unwrap()
+        .contains("Der Link läuft in 60 Minuten ab."));
+
+    let response = client
+        .get("/auth/admin/email-templates/password_reset/preview?locale=xx")
+        .header(Header::new("admin-key", "admin-secret"))
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn writes_emails_in_preferred_locale() {
+    let _delivery = lock_email_delivery();
+    let client = client_with(json!({
+        "email_enabled": true,
+        "email_transport": "memory",
+        "email_outbox_interval": 0
+    }));
+
+    let response = client
+        .post("/auth/register")
+        .header(ContentType::JSON)
+        .header(Header::new("Accept-Language", "de"))
+        .body(
+            json!({
+                "username": "locale_email",
+                "email": "locale_email@gmail.com",
+                "password": "Ibrahim123123",
+                "password_repeat": "Ibrahim123123"
+            })
+            .to_string(),
+        )
+        .dispatch();
+    assert_eq!(response.status(), Status::Created);
+    let email = take_outbox_email(&client, "locale_email@gmail.com");
+    assert_eq!(email.subject, "Bestätige deine E-Mail-Adresse");
+
+    let access_token = get_access_token(&login(&client, "locale_email").1);
+    let (status, body) = update_locale(&client, &access_token, json!("xx"));
+    assert_eq!(status, Status::UnprocessableEntity);
+    assert!(body.unwrap().contains("locale_unsupported"));
+    let (status, _) = update_locale(&client, &access_token, json!("de"));
+    assert_eq!(status, Status::NoContent);
+
+    // The stored preference wins over the English request.
+    let forgot_password = || {
+        client
+            .post("/auth/password/forgot")
+            .header(ContentType::JSON)
+            .header(Header::new("Accept-Language", "en-US"))
+            .body(json!({ "email": "locale_email@gmail.com" }).to_string())
+            .dispatch()
+            .status()
+    };
+    assert_eq!(forgot_password(), Status::Accepted);
+    let email = take_outbox_email(&client, "locale_email@gmail.com");
+    assert_eq!(email.subject, "Setze dein Passwort zurück");
+
+    let (status, _) = update_locale(&client, &access_token, Value::Null);
+    assert_eq!(status, Status::NoContent);
+    assert_eq!(forgot_password(), Status::Accepted);
+    let email = take_outbox_email(&client, "locale_email@gmail.com");
+    assert_eq!(email.subject, "Reset your password");
+}
@@ -1,4 +1,4 @@
-use super::{create_user, get_client};
+use super::{create_user, get_access_token, get_client, get_token_claims};
 use rocket::http::{ContentType, Status};
 
 #[test]
@@ -17,6 +17,7 @@ fn login_user_successfully_with_username() {
     let body = response.into_string().unwrap();
     assert_eq!(body.contains("access_token"), true);
     assert_eq!(body.contains("expires_in"), true);
+    assert!(!body.contains("refresh_token"));
 }
 
 #[test]
@@ -63,3 +64,32 @@ fn fails_login_email_with_wrong_password() {
 
     assert_eq!(response.status(), Status::Unauthorized);
 }
+
+#[test]
+fn login_with_username_and_email_uses_same_subject() {
+    let client = get_client();
+    create_user(&client, "subject_login");
+
+    let username_response = client
+        .post("/auth/login")
+        .header(ContentType::JSON)
+        .body(r#"{ "identifier": "subject_login", "password": "Ibrahim123123" }"#)
+        .dispatch();
+    let email_response = client
+        .post("/auth/login")
+        .header(ContentType::JSON)
+        .body(r#"{ "identifier": "subject_login@gmail.com", "password": "Ibrahim123123" }"#)
+        .dispatch();
+
+    let username_claims = get_token_claims(&get_access_token(&username_response.into_string()));
+    let email_claims = get_token_claims(&get_access_token(&email_response.into_string()));
+
+    assert_eq!(username_claims["sub"], email_claims["sub"]);
+    assert!(username_claims["sub"]
+        .as_str()
+        .unwrap()
+        .parse::<i32>()
+        .is_ok());
+    assert_eq!(username_claims["username"], "subject_login");
+    assert_eq!(email_claims["email"], "subject_login@gmail.com");
+}
@@ -0,0 +1,51 @@
+use super::{create_user, get_access_token, get_client};
+use rocket::http::{ContentType, Cookie, Header, Status};
+
+#[test]
+fn logs_out_session_and_access_token() {
+    let client = get_client();
+    create_user(&client, "logout_user");
+
+    let token_response = client
+        .post("/auth/login")
+        .header(ContentType::JSON)
+        .body(r#"{ "identifier": "logout_user", "password": "Ibrahim123123" }"#)
+        .dispatch();
+    let access_token = get_access_token(&token_response.into_string());
+    let refresh_token = client.cookies().get_private("refresh_token").unwrap();
+
+    let response = client
+        .post("/auth/logout")
+        .header(Header::new("token", format!("Bearer {}", access_token)))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::NoContent);
+    assert!(client.cookies().get_private("refresh_token").is_none());
+
+    let authenticated_response = client
+        .get("/auth/authenticate")
+        .header(Header::new("token", format!("Bearer {}", access_token)))
+        .dispatch();
+
+    assert_eq!(authenticated_response.status(), Status::Unauthorized);
+
+    let refresh_response = client
+        .post("/auth/refresh-token")
+        .header(Header::new("Origin", "http://localhost"))
+        .private_cookie(Cookie::new(
+            "refresh_token",
+            refresh_token.value().to_owned(),
+        ))
+        .dispatch();
+
+    assert_eq!(refresh_response.status(), Status::Unauthorized);
+}
+
+#[test]
+fn does_not_log_out_without_access_token() {
+    let client = get_client();
+
+    let response = client.post("/auth/logout").dispatch();
+
+    assert_eq!(response.status(), Status::Unauthorized);
+}
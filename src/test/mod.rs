@@ -1,26 +1,69 @@
+use crate::email_sender::{Email, Mailer};
+use diesel::{pg::PgConnection, Connection};
+use ring::{rand::SystemRandom, signature::Ed25519KeyPair};
 use rocket::{
-    http::{ContentType, Status},
-    local::blocking::{Client, LocalResponse},
+    figment::providers::Serialized,
+    http::{ContentType, Header, Status},
+    local::blocking::{Client, LocalRequest, LocalResponse},
 };
 use serde_json::{json, Value};
-use std::sync::{Mutex, MutexGuard};
+use std::{
+    sync::{Mutex, MutexGuard},
+    thread, time,
+};
 
+mod account;
+mod account_export;
 mod authenticate;
+mod email_change;
+mod email_outbox;
+mod email_templates;
+mod email_transport;
+mod email_verification;
+mod introspect;
+mod jobs;
+mod jwks;
+mod locale;
 mod login;
+mod logout;
+mod oauth;
+mod password;
 mod refresh_token;
 mod register;
+mod revoke;
+mod sessions;
+mod signing_keys;
+mod username;
 
 pub fn get_access_token(body_string: &Option<String>) -> String {
     let token: Value = serde_json::from_str(body_string.clone().unwrap().as_str()).unwrap();
     token["access_token"].as_str().unwrap().to_owned()
 }
 
+pub fn get_token_claims(token: &str) -> Value {
+    let payload = token.split('.').nth(1).unwrap();
+    let decoded = base64::decode_config(payload, base64::URL_SAFE_NO_PAD).unwrap();
+    serde_json::from_slice(&decoded).unwrap()
+}
+
+/// The user id in the `sub` claim of `token`.
+pub fn get_user_id(token: &str) -> i32 {
+    get_token_claims(token)["sub"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+/// Password of every user made by `create_user`.
+pub const PASSWORD: &str = "Ibrahim123123";
+
 pub fn create_user<'a>(client: &'a Client, username: &str) -> LocalResponse<'a> {
     let json = json!({
         "username": username,
         "email": format!("{}{}", username, "@gmail.com"),
-        "password": "Ibrahim123123",
-        "password_repeat": "Ibrahim123123"
+        "password": PASSWORD,
+        "password_repeat": PASSWORD
     });
 
     let response = client
@@ -34,6 +77,27 @@ pub fn create_user<'a>(client: &'a Client, username: &str) -> LocalResponse<'a>
     response
 }
 
+/// Login request for tests that add headers before dispatching it.
+pub fn login_request<'c>(client: &'c Client, identifier: &str, password: &str) -> LocalRequest<'c> {
+    client
+        .post("/auth/login")
+        .header(ContentType::JSON)
+        .body(json!({ "identifier": identifier, "password": password }).to_string())
+}
+
+/// Signs in as a user made by `create_user`.
+pub fn login(client: &Client, identifier: &str) -> (Status, Option<String>) {
+    let response = login_request(client, identifier, PASSWORD).dispatch();
+
+    (response.status(), response.into_string())
+}
+
+/// Credentials of a configured service client.
+pub fn basic_auth(client_id: &str, client_secret: &str) -> Header<'static> {
+    let credentials = base64::encode(format!("{}:{}", client_id, client_secret));
+    Header::new("Authorization", format!("Basic {}", credentials))
+}
+
 lazy_static::lazy_static! {
     pub static ref ROCKET_CLIENT: Mutex<Client> =
         Mutex::new(Client::tracked(crate::get_rocket()).expect("valid rocket instance"));
@@ -42,3 +106,87 @@ lazy_static::lazy_static! {
 pub fn get_client<'a>() -> MutexGuard<'a, Client> {
     ROCKET_CLIENT.lock().unwrap()
 }
+
+lazy_static::lazy_static! {
+    static ref EMAIL_DELIVERY: Mutex<()> = Mutex::new(());
+}
+
+/// Clients with email enabled deliver the whole shared outbox, so tests holding one take
+/// this lock first to keep their emails out of each other's transports.
+pub fn lock_email_delivery<'a>() -> MutexGuard<'a, ()> {
+    EMAIL_DELIVERY
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Settings that deliver emails to the memory transport every second, with every token
+/// emailed as a `?token=` link. Tests using them hold `lock_email_delivery`.
+pub fn memory_mail_settings() -> Value {
+    json!({
+        "email_enabled": true,
+        "email_outbox_interval": 1,
+        "email_transport": "memory",
+        "password_reset_url": "http://localhost/password/reset",
+        "email_verification_url": "http://localhost/email/verify",
+        "email_change_confirm_url": "http://localhost/email/change/confirm",
+        "email_change_cancel_url": "http://localhost/email/change/cancel"
+    })
+}
+
+/// Waits for the `subject` email to `to` to reach the memory transport.
+pub fn wait_for_email(client: &Client, to: &str, subject: &str) -> Email {
+    let mailer = client.rocket().state::<Mailer>().unwrap();
+
+    for _ in 0..50 {
+        let sent = mailer
+            .sent()
+            .into_iter()
+            .find(|e| e.to == to && e.subject == subject);
+        if let Some(email) = sent {
+            return email;
+        }
+        thread::sleep(time::Duration::from_millis(100));
+    }
+
+    panic!("no \"{}\" email sent to {}", subject, to);
+}
+
+/// The token of the link in the text body of `email`.
+pub fn link_token(email: &Email) -> String {
+    let (_, query) = email
+        .body
+        .split_once("?token=")
+        .unwrap_or_else(|| panic!("no token link in {:?}", email.body));
+
+    query.chars().take_while(|c| !c.is_whitespace()).collect()
+}
+
+/// A client of its own with the `settings` object merged over the configuration.
+pub fn client_with(settings: Value) -> Client {
+    let figment = rocket::Config::figment().merge(Serialized::globals(settings));
+
+    Client::tracked(crate::build_rocket(rocket::custom(figment))).expect("valid rocket instance")
+}
+
+/// Direct database access for state the API does not expose.
+pub fn get_connection(client: &Client) -> PgConnection {
+    let database_url = client
+        .rocket()
+        .figment()
+        .extract_inner::<String>("databases.pg_conn.url")
+        .unwrap();
+
+    PgConnection::establish(&database_url).unwrap()
+}
+
+pub fn write_ed25519_key(name: &str) -> String {
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+    let private_pem = pem::encode(&pem::Pem {
+        tag: "PRIVATE KEY".to_owned(),
+        contents: pkcs8.as_ref().to_vec(),
+    });
+    let key_file = std::env::temp_dir().join(format!("profile-service-{}.pem", name));
+    std::fs::write(&key_file, private_pem).unwrap();
+
+    key_file.to_str().unwrap().to_owned()
+}
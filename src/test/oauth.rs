@@ -0,0 +1,404 @@
+use super::{
+    client_with, create_user, get_access_token, get_connection, get_token_claims, link_token,
+    lock_email_delivery, login, memory_mail_settings, wait_for_email,
+};
+use crate::schema::{security_events, users};
+use diesel::prelude::*;
+use rocket::{
+    http::{ContentType, Header, Status},
+    local::blocking::Client,
+};
+use serde_json::{json, Value};
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    thread,
+};
+
+/// Serves the Twitch token endpoint and Helix `users` for `twitch_user` on a local port, and
+/// returns the settings pointing the service at it.
+fn mock_twitch(twitch_user: Value) -> Value {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = format!("http://{}", listener.local_addr().unwrap());
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut content_length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+
+            let response = match request_line.split_whitespace().nth(1) {
+                Some("/oauth2/token") => json!({
+                    "access_token": "twitch-access",
+                    "refresh_token": "twitch-refresh",
+                    "expires_in": 3600,
+                    "scope": ["openid"],
+                    "token_type": "bearer"
+                }),
+                _ => json!({ "data": [twitch_user] }),
+            }
+            .to_string();
+
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                response.len(),
+                response
+            )
+            .unwrap();
+        }
+    });
+
+    json!({
+        "twitch_id_url": format!("{}/oauth2", address),
+        "twitch_api_url": format!("{}/helix", address),
+    })
+}
+
+/// Starts the sign-in for the `state` Twitch would pass back to the callback.
+fn start_twitch_login(client: &Client) -> String {
+    let response = client.get("/auth/oauth/twitch").dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+
+    let location = response.headers().get_one("Location").unwrap();
+    let (_, query) = location.split_once("state=").unwrap();
+    query.split('&').next().unwrap().to_owned()
+}
+
+fn post_twitch_code(client: &Client, state: &str, token: Option<&str>) -> (Status, Option<String>) {
+    let mut request = client
+        .post("/auth/oauth/twitch")
+        .header(ContentType::JSON)
+        .body(json!({ "grant_type": "code", "code": "twitch-code", "state": state }).to_string());
+    if let Some(token) = token {
+        request = request.header(Header::new("token", format!("Bearer {}", token)));
+    }
+    let response = request.dispatch();
+
+    (response.status(), response.into_string())
+}
+
+fn twitch_login(client: &Client) -> (Status, Option<String>) {
+    let state = start_twitch_login(client);
+    post_twitch_code(client, &state, None)
+}
+
+fn mark_verified(client: &Client, username: &str) {
+    diesel::update(users::table.filter(users::username.eq(username)))
+        .set(users::email_verified_at.eq(chrono::Utc::now().naive_utc()))
+        .execute(&get_connection(client))
+        .unwrap();
+}
+
+#[test]
+fn creates_account_on_first_twitch_login() {
+    let client = client_with(mock_twitch(json!({
+        "id": "twitch-1001",
+        "login": "twitch_first",
+        "email": "Twitch_First@Example.com"
+    })));
+
+    let (status, body) = twitch_login(&client);
+    assert_eq!(status, Status::Ok);
+    let claims = get_token_claims(&get_access_token(&body));
+    assert_eq!(claims["username"], "twitch_first");
+    assert_eq!(claims["email"], "twitch_first@example.com");
+    assert!(client
+        .cookies()
+        .get_private("twitch_refresh_token")
+        .is_some());
+
+    let verified_at = users::table
+        .select(users::email_verified_at)
+        .filter(users::username.eq("twitch_first"))
+        .first::<Option<chrono::NaiveDateTime>>(&get_connection(&client))
+        .unwrap();
+    assert!(verified_at.is_some());
+
+    let (status, body) = twitch_login(&client);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(
+        get_token_claims(&get_access_token(&body))["sub"],
+        claims["sub"]
+    );
+}
+
+#[test]
+fn suffixes_taken_username_of_new_twitch_account() {
+    let client = client_with(mock_twitch(json!({
+        "id": "twitch-1002",
+        "login": "twitch_taken",
+        "email": "twitch_taken@example.com"
+    })));
+    create_user(&client, "twitch_taken");
+
+    let (status, body) = twitch_login(&client);
+
+    assert_eq!(status, Status::Ok);
+    assert_eq!(
+        get_token_claims(&get_access_token(&body))["username"],
+        "twitch_taken2"
+    );
+}
+
+#[test]
+fn links_twitch_to_signed_in_account_with_same_email() {
+    let client = client_with(mock_twitch(json!({
+        "id": "twitch-1003",
+        "login": "someone_else",
+        "email": "twitch_linked@gmail.com"
+    })));
+    create_user(&client, "twitch_linked");
+    let access_token = get_access_token(&login(&client, "twitch_linked").1);
+    let password_claims = get_token_claims(&access_token);
+
+    let state = start_twitch_login(&client);
+    let (status, body) = post_twitch_code(&client, &state, Some(&access_token));
+
+    assert_eq!(status, Status::Ok);
+    assert_eq!(
+        get_token_claims(&get_access_token(&body))["sub"],
+        password_claims["sub"]
+    );
+
+    let user_id = password_claims["sub"]
+        .as_str()
+        .unwrap()
+        .parse::<i32>()
+        .unwrap();
+    let events = security_events::table
+        .select(security_events::event_type)
+        .filter(security_events::user_id.eq(user_id))
+        .load::<String>(&get_connection(&client))
+        .unwrap();
+    assert!(events.contains(&"identity_linked".to_owned()));
+
+    // Once linked, the Twitch account signs in without the password session.
+    let (status, body) = twitch_login(&client);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(
+        get_token_claims(&get_access_token(&body))["sub"],
+        password_claims["sub"]
+    );
+}
+
+#[test]
+fn does_not_link_twitch_to_account_without_sign_in() {
+    let client = client_with(mock_twitch(json!({
+        "id": "twitch-1004",
+        "login": "twitch_unlinked_other",
+        "email": "twitch_unlinked@gmail.com"
+    })));
+    create_user(&client, "twitch_unlinked");
+    mark_verified(&client, "twitch_unlinked");
+
+    let (status, body) = twitch_login(&client);
+
+    assert_eq!(status, Status::Conflict);
+    assert!(body.unwrap().contains("twitch_link_requires_login"));
+}
+
+#[test]
+fn rejects_twitch_code_without_matching_state() {
+    let client = client_with(mock_twitch(json!({
+        "id": "twitch-1007",
+        "login": "twitch_state",
+        "email": "twitch_state@example.com"
+    })));
+
+    let (status, _) = post_twitch_code(&client, "forged-state", None);
+    assert_eq!(status, Status::Unauthorized);
+
+    start_twitch_login(&client);
+    let (status, _) = post_twitch_code(&client, "forged-state", None);
+    assert_eq!(status, Status::Unauthorized);
+
+    // The state is consumed by the first attempt.
+    let state = start_twitch_login(&client);
+    assert_eq!(post_twitch_code(&client, &state, None).0, Status::Ok);
+    assert_eq!(
+        post_twitch_code(&client, &state, None).0,
+        Status::Unauthorized
+    );
+}
+
+#[test]
+fn twitch_account_sets_password_through_reset_before_deletion() {
+    let _delivery = lock_email_delivery();
+    let mut settings = memory_mail_settings();
+    settings.as_object_mut().unwrap().extend(
+        mock_twitch(json!({
+            "id": "twitch-1008",
+            "login": "twitch_password",
+            "email": "twitch_password@example.com"
+        }))
+        .as_object()
+        .unwrap()
+        .clone(),
+    );
+    let client = client_with(settings);
+    let access_token = get_access_token(&twitch_login(&client).1);
+
+    // The account has a random password nobody knows.
+    let delete_account = |password: &str| {
+        client
+            .delete("/auth/account")
+            .header(ContentType::JSON)
+            .header(Header::new("token", format!("Bearer {}", access_token)))
+            .body(json!({ "password": password }).to_string())
+            .dispatch()
+            .status()
+    };
+    assert_eq!(delete_account("Ibrahim123123"), Status::Forbidden);
+
+    let response = client
+        .post("/auth/password/forgot")
+        .header(ContentType::JSON)
+        .body(json!({ "email": "twitch_password@example.com" }).to_string())
+        .dispatch();
+    assert_eq!(response.status(), Status::Accepted);
+    let token = link_token(&wait_for_email(
+        &client,
+        "twitch_password@example.com",
+        "Reset your password",
+    ));
+    let response = client
+        .post("/auth/password/reset")
+        .header(ContentType::JSON)
+        .body(
+            json!({
+                "token": token,
+                "password": "Ibrahim123123",
+                "password_repeat": "Ibrahim123123"
+            })
+            .to_string(),
+        )
+        .dispatch();
+    assert_eq!(response.status(), Status::NoContent);
+
+    assert_eq!(delete_account("Ibrahim123123"), Status::NoContent);
+}
+
+#[test]
+fn fails_twitch_login_without_email() {
+    let client = client_with(mock_twitch(json!({
+        "id": "twitch-1005",
+        "login": "twitch_no_email"
+    })));
+
+    let (status, body) = twitch_login(&client);
+
+    assert_eq!(status, Status::UnprocessableEntity);
+    assert!(body.unwrap().contains("twitch_email_unavailable"));
+}
+
+#[test]
+fn refreshes_own_token_after_twitch_login() {
+    let client = client_with(mock_twitch(json!({
+        "id": "twitch-1006",
+        "login": "twitch_refresh",
+        "email": "twitch_refresh@example.com"
+    })));
+    twitch_login(&client);
+
+    let response = client
+        .post("/auth/refresh-token")
+        .header(Header::new("Origin", "http://localhost"))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let claims = get_token_claims(&get_access_token(&response.into_string()));
+    assert_eq!(claims["username"], "twitch_refresh");
+}
+
+#[test]
+fn returns_refresh_token_in_body_when_asked() {
+    let client = client_with(mock_twitch(json!({
+        "id": "twitch-1009",
+        "login": "twitch_body",
+        "email": "twitch_body@example.com"
+    })));
+    let state = start_twitch_login(&client);
+
+    let response = client
+        .post("/auth/oauth/twitch")
+        .header(ContentType::JSON)
+        .body(
+            json!({
+                "grant_type": "code",
+                "code": "twitch-code",
+                "state": state,
+                "refresh_token_in_body": true
+            })
+            .to_string(),
+        )
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let body: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    let refresh_token = body["refresh_token"].as_str().unwrap();
+
+    let response = client
+        .post("/auth/refresh-token")
+        .header(ContentType::JSON)
+        .body(json!({ "refresh_token": refresh_token }).to_string())
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[test]
+fn alerts_on_twitch_sign_in_from_new_browser() {
+    let _delivery = lock_email_delivery();
+    let mut settings = memory_mail_settings();
+    settings.as_object_mut().unwrap().extend(
+        mock_twitch(json!({
+            "id": "twitch-1010",
+            "login": "twitch_alert",
+            "email": "twitch_alert@example.com"
+        }))
+        .as_object()
+        .unwrap()
+        .clone(),
+    );
+    let client = client_with(settings);
+
+    for user_agent in &[
+        "Mozilla/5.0 (X11; Linux x86_64) Firefox/90.0",
+        "Mozilla/5.0 (Windows NT 10.0) Chrome/91.0",
+    ] {
+        let state = start_twitch_login(&client);
+        let response = client
+            .post("/auth/oauth/twitch")
+            .header(ContentType::JSON)
+            .header(Header::new("User-Agent", *user_agent))
+            .body(
+                json!({ "grant_type": "code", "code": "twitch-code", "state": state }).to_string(),
+            )
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    let email = wait_for_email(
+        &client,
+        "twitch_alert@example.com",
+        "New sign-in to your account",
+    );
+    assert!(email.body.contains("Chrome on Windows"));
+}
@@ -0,0 +1,225 @@
+use super::{
+    client_with, create_user, get_access_token, get_client, link_token, lock_email_delivery, login,
+    login_request, memory_mail_settings, wait_for_email,
+};
+use rocket::{
+    http::{ContentType, Header, Status},
+    local::blocking::Client,
+};
+use serde_json::json;
+
+fn request_reset_token(client: &Client, username: &str) -> String {
+    let email = format!("{}@gmail.com", username);
+    let response = client
+        .post("/auth/password/forgot")
+        .header(ContentType::JSON)
+        .body(json!({ "email": email }).to_string())
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Accepted);
+
+    link_token(&wait_for_email(client, &email, "Reset your password"))
+}
+
+fn reset_password(client: &Client, token: &str, password: &str) -> Status {
+    client
+        .post("/auth/password/reset")
+        .header(ContentType::JSON)
+        .body(
+            json!({ "token": token, "password": password, "password_repeat": password })
+                .to_string(),
+        )
+        .dispatch()
+        .status()
+}
+
+#[test]
+fn accepts_forgot_password_for_unknown_email() {
+    let client = get_client();
+
+    let response = client
+        .post("/auth/password/forgot")
+        .header(ContentType::JSON)
+        .body(r#"{ "email": "password_unknown@gmail.com" }"#)
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Accepted);
+}
+
+#[test]
+fn resets_password_and_revokes_sessions() {
+    let _delivery = lock_email_delivery();
+    let client = client_with(memory_mail_settings());
+    create_user(&client, "password_reset");
+
+    client
+        .post("/auth/login")
+        .header(ContentType::JSON)
+        .body(r#"{ "identifier": "password_reset", "password": "Ibrahim123123" }"#)
+        .dispatch();
+
+    let token = request_reset_token(&client, "password_reset");
+
+    assert_eq!(
+        reset_password(&client, &token, "NewPassword123"),
+        Status::NoContent
+    );
+
+    let refresh_response = client
+        .post("/auth/refresh-token")
+        .header(Header::new("Origin", "http://localhost"))
+        .dispatch();
+
+    assert_eq!(refresh_response.status(), Status::Unauthorized);
+
+    let old_password_response = client
+        .post("/auth/login")
+        .header(ContentType::JSON)
+        .body(r#"{ "identifier": "password_reset", "password": "Ibrahim123123" }"#)
+        .dispatch();
+
+    assert_eq!(old_password_response.status(), Status::Unauthorized);
+
+    let new_password_response = client
+        .post("/auth/login")
+        .header(ContentType::JSON)
+        .body(r#"{ "identifier": "password_reset", "password": "NewPassword123" }"#)
+        .dispatch();
+
+    assert_eq!(new_password_response.status(), Status::Ok);
+}
+
+#[test]
+fn does_not_reuse_reset_token() {
+    let _delivery = lock_email_delivery();
+    let client = client_with(memory_mail_settings());
+    create_user(&client, "password_reset_reuse");
+
+    let token = request_reset_token(&client, "password_reset_reuse");
+
+    assert_eq!(
+        reset_password(&client, &token, "NewPassword123"),
+        Status::NoContent
+    );
+    assert_eq!(
+        reset_password(&client, &token, "OtherPassword123"),
+        Status::BadRequest
+    );
+}
+
+#[test]
+fn does_not_reset_with_weak_password() {
+    let _delivery = lock_email_delivery();
+    let client = client_with(memory_mail_settings());
+    create_user(&client, "password_reset_weak");
+
+    let token = request_reset_token(&client, "password_reset_weak");
+
+    assert_eq!(
+        reset_password(&client, &token, "short"),
+        Status::UnprocessableEntity
+    );
+    assert_eq!(
+        reset_password(&client, &token, "NewPassword123"),
+        Status::NoContent
+    );
+}
+
+#[test]
+fn does_not_reset_with_invalid_token() {
+    let client = get_client();
+
+    assert_eq!(
+        reset_password(&client, "invalid", "NewPassword123"),
+        Status::BadRequest
+    );
+}
+
+fn change_password(client: &Client, access_token: &str, current: &str, password: &str) -> Status {
+    client
+        .post("/auth/password/change")
+        .header(ContentType::JSON)
+        .header(Header::new("token", format!("Bearer {}", access_token)))
+        .body(
+            json!({
+                "current_password": current,
+                "password": password,
+                "password_repeat": password
+            })
+            .to_string(),
+        )
+        .dispatch()
+        .status()
+}
+
+#[test]
+fn changes_password_and_revokes_other_sessions() {
+    let client = get_client();
+    create_user(&client, "password_change");
+
+    login(&client, "password_change");
+    let other_refresh_token = client.cookies().get_private("refresh_token").unwrap();
+    let (_, body) = login(&client, "password_change");
+    let access_token = get_access_token(&body);
+
+    assert_eq!(
+        change_password(&client, &access_token, "Ibrahim123123", "NewPassword123"),
+        Status::NoContent
+    );
+
+    let other_session_response = client
+        .post("/auth/refresh-token")
+        .header(ContentType::JSON)
+        .body(json!({ "refresh_token": other_refresh_token.value() }).to_string())
+        .dispatch();
+
+    assert_eq!(other_session_response.status(), Status::Unauthorized);
+
+    let current_session_response = client
+        .post("/auth/refresh-token")
+        .header(Header::new("Origin", "http://localhost"))
+        .dispatch();
+
+    assert_eq!(current_session_response.status(), Status::Ok);
+    assert_eq!(login(&client, "password_change").0, Status::Unauthorized);
+    assert_eq!(
+        login_request(&client, "password_change", "NewPassword123")
+            .dispatch()
+            .status(),
+        Status::Ok
+    );
+}
+
+#[test]
+fn does_not_change_password_with_wrong_current_password() {
+    let client = get_client();
+    create_user(&client, "password_change_wrong");
+
+    let (_, body) = login(&client, "password_change_wrong");
+    let access_token = get_access_token(&body);
+
+    assert_eq!(
+        change_password(&client, &access_token, "WrongPassword123", "NewPassword123"),
+        Status::Forbidden
+    );
+    assert_eq!(
+        change_password(&client, &access_token, "Ibrahim123123", "short"),
+        Status::UnprocessableEntity
+    );
+    assert_eq!(login(&client, "password_change_wrong").0, Status::Ok);
+}
+
+#[test]
+fn does_not_change_password_without_access_token() {
+    let client = get_client();
+
+    let response = client
+        .post("/auth/password/change")
+        .header(ContentType::JSON)
+        .body(
+            r#"{ "current_password": "Ibrahim123123", "password": "NewPassword123", "password_repeat": "NewPassword123" }"#,
+        )
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Unauthorized);
+}
@@ -1,25 +1,32 @@
-use super::{create_user, get_access_token, get_client};
-use rocket::http::{ContentType, Header, Status};
+use super::{create_user, get_access_token, get_client, get_connection, get_user_id, login};
+use crate::{
+    schema::refresh_tokens,
+    util::{globals::GlobalConfig, token_hash::hash_token},
+};
+use diesel::prelude::*;
+use rocket::http::{ContentType, Cookie, Header, Status};
 use std::{thread, time};
 
+fn get_refresh_token(body: &Option<String>) -> String {
+    let json: serde_json::Value = serde_json::from_str(body.as_ref().unwrap()).unwrap();
+    json["refresh_token"].as_str().unwrap().to_owned()
+}
+
 #[test]
 fn refreshes_token_generates_correct_token_successfully() {
     let client = get_client();
     create_user(&client, "refrestokenuser");
 
-    let token_response = client
+    client
         .post("/auth/login")
         .header(ContentType::JSON)
         .body(r#"{ "identifier": "refrestokenuser", "password": "Ibrahim123123" }"#)
         .dispatch();
 
-    let mut request = client.get("/auth/refresh-token").header(ContentType::JSON);
-
-    let access_token = get_access_token(&token_response.into_string());
-    let header_token = Header::new("token", format!("Bearer {}", access_token));
-    request.add_header(header_token);
-
-    let response = request.dispatch();
+    let response = client
+        .post("/auth/refresh-token")
+        .header(Header::new("Origin", "http://localhost"))
+        .dispatch();
 
     assert_eq!(response.status(), Status::Ok);
 
@@ -32,6 +39,99 @@ fn refreshes_token_generates_correct_token_successfully() {
     assert_eq!(authenticated_response.status(), Status::Ok);
 }
 
+#[test]
+fn stores_only_hash_of_refresh_token() {
+    let client = get_client();
+    create_user(&client, "refresh_token_hashed");
+
+    let user_id = get_user_id(&get_access_token(&login(&client, "refresh_token_hashed").1));
+    let refresh_token = client.cookies().get_private("refresh_token").unwrap();
+
+    let token_hashes = refresh_tokens::table
+        .select(refresh_tokens::token_hash)
+        .filter(refresh_tokens::user_id.eq(user_id))
+        .load::<String>(&get_connection(&client))
+        .unwrap();
+
+    let global_config = client.rocket().state::<GlobalConfig>().unwrap();
+    assert_eq!(
+        token_hashes,
+        vec![hash_token(
+            refresh_token.value(),
+            global_config.token_hash_key()
+        )]
+    );
+    assert_ne!(token_hashes[0], refresh_token.value());
+}
+
+#[test]
+fn refreshes_token_from_request_body() {
+    let client = get_client();
+    create_user(&client, "refresh_token_body");
+
+    let login_response = client
+        .post("/auth/login")
+        .header(ContentType::JSON)
+        .body(
+            r#"{ "identifier": "refresh_token_body", "password": "Ibrahim123123", "refresh_token_in_body": true }"#,
+        )
+        .dispatch();
+    let refresh_token = get_refresh_token(&login_response.into_string());
+
+    let response = client
+        .post("/auth/refresh-token")
+        .header(ContentType::JSON)
+        .body(format!(r#"{{ "refresh_token": "{}" }}"#, refresh_token))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+
+    let body = response.into_string();
+    let rotated_refresh_token = get_refresh_token(&body);
+    assert_ne!(rotated_refresh_token, refresh_token);
+
+    let rotated_response = client
+        .post("/auth/refresh-token")
+        .header(ContentType::JSON)
+        .body(format!(
+            r#"{{ "refresh_token": "{}" }}"#,
+            rotated_refresh_token
+        ))
+        .dispatch();
+
+    assert_eq!(rotated_response.status(), Status::Ok);
+}
+
+#[test]
+fn does_not_refresh_token_from_cookie_without_allowed_origin() {
+    let client = get_client();
+    create_user(&client, "refresh_token_no_origin");
+
+    client
+        .post("/auth/login")
+        .header(ContentType::JSON)
+        .body(r#"{ "identifier": "refresh_token_no_origin", "password": "Ibrahim123123" }"#)
+        .dispatch();
+
+    let missing_origin_response = client.post("/auth/refresh-token").dispatch();
+
+    assert_eq!(missing_origin_response.status(), Status::Forbidden);
+
+    let foreign_origin_response = client
+        .post("/auth/refresh-token")
+        .header(Header::new("Referer", "http://evil.example/page"))
+        .dispatch();
+
+    assert_eq!(foreign_origin_response.status(), Status::Forbidden);
+
+    let referer_response = client
+        .post("/auth/refresh-token")
+        .header(Header::new("Referer", "http://localhost/profile"))
+        .dispatch();
+
+    assert_eq!(referer_response.status(), Status::Ok);
+}
+
 #[test]
 fn does_not_refreshes_token_with_invalid_token() {
     let client = get_client();
@@ -43,34 +143,76 @@ fn does_not_refreshes_token_with_invalid_token() {
         .body(r#"{ "identifier": "refresh_token_incorrect_token", "password": "Ibrahim123123" }"#)
         .dispatch();
 
-    let mut request = client.get("/auth/refresh-token").header(ContentType::JSON);
-
-    request.add_header(Header::new("token", "incorrect token"));
-
-    let response = request.dispatch();
+    let response = client
+        .post("/auth/refresh-token")
+        .header(ContentType::JSON)
+        .body(r#"{ "refresh_token": "incorrect token" }"#)
+        .dispatch();
 
     assert_eq!(response.status(), Status::Unauthorized);
 }
 
 #[test]
 #[ignore]
-fn system_time_does_not_refresh_token_with_expired_token() {
+fn system_time_refreshes_token_after_access_token_expired() {
     let client = get_client();
     create_user(&client, "refresh_token_expired");
-    let token_response = client
+    client
         .post("/auth/login")
         .header(ContentType::JSON)
         .body(r#"{ "identifier": "refresh_token_expired", "password": "Ibrahim123123" }"#)
         .dispatch();
 
-    let access_token = get_access_token(&token_response.into_string());
+    thread::sleep(time::Duration::from_millis(4000));
+    let response = client
+        .post("/auth/refresh-token")
+        .header(Header::new("Origin", "http://localhost"))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[test]
+fn revokes_token_family_when_rotated_token_is_reused() {
+    let client = get_client();
+    create_user(&client, "refresh_token_reuse");
+
+    client
+        .post("/auth/login")
+        .header(ContentType::JSON)
+        .body(r#"{ "identifier": "refresh_token_reuse", "password": "Ibrahim123123" }"#)
+        .dispatch();
+
+    let first_refresh_token = client.cookies().get_private("refresh_token").unwrap();
 
-    let mut request = client.get("/auth/refresh-token").header(ContentType::JSON);
+    let response = client
+        .post("/auth/refresh-token")
+        .header(Header::new("Origin", "http://localhost"))
+        .dispatch();
 
-    request.add_header(Header::new("token", format!("Bearer {}", access_token)));
+    assert_eq!(response.status(), Status::Ok);
 
-    thread::sleep(time::Duration::from_millis(4000));
-    let response = request.dispatch();
+    let second_refresh_token = client.cookies().get_private("refresh_token").unwrap();
 
-    assert_eq!(response.status(), Status::Unauthorized);
+    let reused_response = client
+        .post("/auth/refresh-token")
+        .header(Header::new("Origin", "http://localhost"))
+        .private_cookie(Cookie::new(
+            "refresh_token",
+            first_refresh_token.value().to_owned(),
+        ))
+        .dispatch();
+
+    assert_eq!(reused_response.status(), Status::Unauthorized);
+
+    let revoked_response = client
+        .post("/auth/refresh-token")
+        .header(Header::new("Origin", "http://localhost"))
+        .private_cookie(Cookie::new(
+            "refresh_token",
+            second_refresh_token.value().to_owned(),
+        ))
+        .dispatch();
+
+    assert_eq!(revoked_response.status(), Status::Unauthorized);
 }
@@ -1,4 +1,12 @@
-use super::get_client;
+use super::{get_client, get_connection};
+use crate::{
+    database::DbConn, models::user::NewUser, repository::user::insert, schema::users,
+    util::response::Error,
+};
+use diesel::{
+    prelude::*,
+    result::{DatabaseErrorKind, Error as DieselError},
+};
 use rocket::http::{ContentType, Status};
 
 #[test]
@@ -103,3 +111,114 @@ fn cannot_create_user_with_incorrect_email() {
     let body = response.into_string().unwrap();
     assert_eq!(body.contains("email_invalid"), true);
 }
+
+#[test]
+fn compares_username_and_email_case_insensitively() {
+    let client = get_client();
+    let response = client
+        .post("/auth/register")
+        .header(ContentType::JSON)
+        .body(r#"{ "username": "CaseUser", "email": "CaseUser@Gmail.com", "password": "Ibrahim123123", "password_repeat": "Ibrahim123123" }"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::Created);
+
+    let response = client
+        .post("/auth/register")
+        .header(ContentType::JSON)
+        .body(r#"{ "username": "caseuser", "email": "caseuser@gmail.com", "password": "Ibrahim123123", "password_repeat": "Ibrahim123123" }"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::Conflict);
+    let body = response.into_string().unwrap();
+    assert!(body.contains("username_exists"));
+    assert!(body.contains("email_exists"));
+
+    let email: String = users::table
+        .select(users::email)
+        .filter(users::username.eq("CaseUser"))
+        .first(&get_connection(&client))
+        .unwrap();
+    assert_eq!(email, "caseuser@gmail.com");
+
+    let response = client
+        .post("/auth/login")
+        .header(ContentType::JSON)
+        .body(r#"{ "identifier": "CASEUSER@gmail.com", "password": "Ibrahim123123" }"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[test]
+fn database_rejects_case_variants_of_existing_users() {
+    let client = get_client();
+    let response = client
+        .post("/auth/register")
+        .header(ContentType::JSON)
+        .body(r#"{ "username": "IndexUser", "email": "indexuser@gmail.com", "password": "Ibrahim123123", "password_repeat": "Ibrahim123123" }"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::Created);
+
+    let result = diesel::insert_into(users::table)
+        .values((
+            users::username.eq("INDEXUSER"),
+            users::email.eq("other_indexuser@gmail.com"),
+            users::password.eq("hash"),
+        ))
+        .execute(&get_connection(&client));
+
+    match result {
+        Err(DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, info)) => {
+            assert_eq!(info.constraint_name(), Some("users_username_lower_key"))
+        }
+        other => panic!("expected a unique violation, got {:?}", other),
+    }
+}
+
+#[test]
+fn insert_reports_index_conflicts_as_duplicates() {
+    let client = get_client();
+    let response = client
+        .post("/auth/register")
+        .header(ContentType::JSON)
+        .body(r#"{ "username": "InsertIndexUser", "email": "insertindexuser@gmail.com", "password": "Ibrahim123123", "password_repeat": "Ibrahim123123" }"#)
+        .dispatch();
+    assert_eq!(response.status(), Status::Created);
+
+    let insert_user = |username: &str, email: &str| {
+        let user = NewUser {
+            username: username.to_owned(),
+            email: email.to_owned(),
+            password: "hash".to_owned(),
+        };
+        rocket::tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let conn = DbConn::get_one(client.rocket()).await.unwrap();
+                insert(&conn, user, |_| vec![]).await
+            })
+    };
+
+    for (username, email, code) in [
+        (
+            "INSERTINDEXUSER",
+            "other_insertindexuser@gmail.com",
+            "username_exists",
+        ),
+        (
+            "other_insertindexuser",
+            "InsertIndexUser@gmail.com",
+            "email_exists",
+        ),
+    ]
+    .iter()
+    {
+        match insert_user(username, email) {
+            Err(Error::ErrorWithBody(response)) => {
+                assert_eq!(response.status, Status::Conflict);
+                assert_eq!(response.json.error_codes, Some(vec![code.to_string()]));
+            }
+            other => panic!("expected {}, got {:?}", code, other.map(|u| u.id)),
+        }
+    }
+}
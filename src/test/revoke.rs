@@ -0,0 +1,74 @@
+use super::{create_user, get_access_token, get_client};
+use rocket::http::{ContentType, Header, Status};
+
+#[test]
+fn revokes_access_token() {
+    let client = get_client();
+    create_user(&client, "revoke_access");
+
+    let token_response = client
+        .post("/auth/login")
+        .header(ContentType::JSON)
+        .body(r#"{ "identifier": "revoke_access", "password": "Ibrahim123123" }"#)
+        .dispatch();
+    let access_token = get_access_token(&token_response.into_string());
+
+    let response = client
+        .post("/auth/revoke")
+        .header(ContentType::Form)
+        .body(format!(
+            "token={}&token_type_hint=access_token",
+            access_token
+        ))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+
+    let authenticated_response = client
+        .get("/auth/authenticate")
+        .header(Header::new("token", format!("Bearer {}", access_token)))
+        .dispatch();
+
+    assert_eq!(authenticated_response.status(), Status::Unauthorized);
+}
+
+#[test]
+fn revokes_refresh_token() {
+    let client = get_client();
+    create_user(&client, "revoke_refresh");
+
+    client
+        .post("/auth/login")
+        .header(ContentType::JSON)
+        .body(r#"{ "identifier": "revoke_refresh", "password": "Ibrahim123123" }"#)
+        .dispatch();
+    let refresh_token = client.cookies().get_private("refresh_token").unwrap();
+
+    let response = client
+        .post("/auth/revoke")
+        .header(ContentType::Form)
+        .body(format!("token={}", refresh_token.value()))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+
+    let refresh_response = client
+        .post("/auth/refresh-token")
+        .header(Header::new("Origin", "http://localhost"))
+        .dispatch();
+
+    assert_eq!(refresh_response.status(), Status::Unauthorized);
+}
+
+#[test]
+fn accepts_revocation_of_invalid_token() {
+    let client = get_client();
+
+    let response = client
+        .post("/auth/revoke")
+        .header(ContentType::Form)
+        .body("token=invalid_token")
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+}
@@ -0,0 +1,114 @@
+use super::{create_user, get_access_token, get_client, login_request, PASSWORD};
+use rocket::{
+    http::{Cookie, Header, Status},
+    local::blocking::Client,
+};
+use serde_json::Value;
+
+const FIREFOX_LINUX: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:91.0) Gecko/20100101 Firefox/91.0";
+
+fn login_from(client: &Client, username: &str, user_agent: &str) -> (String, String) {
+    let token_response = login_request(client, username, PASSWORD)
+        .header(Header::new("User-Agent", user_agent.to_owned()))
+        .dispatch();
+    let access_token = get_access_token(&token_response.into_string());
+    let refresh_token = client.cookies().get_private("refresh_token").unwrap();
+
+    (access_token, refresh_token.value().to_owned())
+}
+
+fn get_sessions(client: &Client, access_token: &str) -> Vec<Value> {
+    let response = client
+        .get("/auth/sessions")
+        .header(Header::new("token", format!("Bearer {}", access_token)))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+
+    serde_json::from_str(&response.into_string().unwrap()).unwrap()
+}
+
+#[test]
+fn lists_sessions_with_device_details() {
+    let client = get_client();
+    create_user(&client, "sessions_list");
+
+    login_from(&client, "sessions_list", "curl/7.68.0");
+    let (access_token, _) = login_from(&client, "sessions_list", FIREFOX_LINUX);
+
+    let sessions = get_sessions(&client, &access_token);
+
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions[0]["label"], "Firefox on Linux");
+    assert_eq!(sessions[0]["user_agent"], FIREFOX_LINUX);
+    assert_eq!(sessions[0]["current"], true);
+    assert_eq!(sessions[1]["label"], "Unknown device");
+    assert_eq!(sessions[1]["current"], false);
+}
+
+#[test]
+fn revokes_single_session() {
+    let client = get_client();
+    create_user(&client, "sessions_revoke_one");
+
+    let (_, other_refresh_token) = login_from(&client, "sessions_revoke_one", FIREFOX_LINUX);
+    let (access_token, _) = login_from(&client, "sessions_revoke_one", FIREFOX_LINUX);
+    let other_session = get_sessions(&client, &access_token)
+        .into_iter()
+        .find(|s| s["current"] == false)
+        .unwrap();
+
+    let response = client
+        .delete(format!(
+            "/auth/sessions/{}",
+            other_session["id"].as_str().unwrap()
+        ))
+        .header(Header::new("token", format!("Bearer {}", access_token)))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::NoContent);
+    assert_eq!(get_sessions(&client, &access_token).len(), 1);
+
+    let refresh_response = client
+        .post("/auth/refresh-token")
+        .header(Header::new("Origin", "http://localhost"))
+        .private_cookie(Cookie::new("refresh_token", other_refresh_token))
+        .dispatch();
+
+    assert_eq!(refresh_response.status(), Status::Unauthorized);
+
+    let unknown_response = client
+        .delete("/auth/sessions/unknown")
+        .header(Header::new("token", format!("Bearer {}", access_token)))
+        .dispatch();
+
+    assert_eq!(unknown_response.status(), Status::NotFound);
+}
+
+#[test]
+fn revokes_all_other_sessions() {
+    let client = get_client();
+    create_user(&client, "sessions_revoke_all");
+
+    login_from(&client, "sessions_revoke_all", FIREFOX_LINUX);
+    login_from(&client, "sessions_revoke_all", FIREFOX_LINUX);
+    let (access_token, _) = login_from(&client, "sessions_revoke_all", FIREFOX_LINUX);
+
+    let response = client
+        .delete("/auth/sessions")
+        .header(Header::new("token", format!("Bearer {}", access_token)))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::NoContent);
+
+    let sessions = get_sessions(&client, &access_token);
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0]["current"], true);
+
+    let refresh_response = client
+        .post("/auth/refresh-token")
+        .header(Header::new("Origin", "http://localhost"))
+        .dispatch();
+
+    assert_eq!(refresh_response.status(), Status::Ok);
+}
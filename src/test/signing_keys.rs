@@ -0,0 +1,147 @@
+use super::{client_with, create_user, get_access_token, login, write_ed25519_key};
+use crate::{
+    jwt::KeyError,
+    util::globals::{JWTConfig, SigningConfig},
+};
+use jsonwebtoken::{decode_header, encode, EncodingKey, Header as JwtHeader};
+use rocket::http::{Header, Status};
+use serde_json::{json, Value};
+
+/// Promotions are shared through the database, so every test rotates between its own
+/// `<prefix>-old` and `<prefix>-new` keys.
+fn keyring_settings(prefix: &str) -> Value {
+    json!({
+        "admin_api_key": "admin-secret",
+        "jwt_keys": [
+            { "kid": format!("{}-old", prefix), "secret": "old-secret" },
+            {
+                "kid": format!("{}-new", prefix),
+                "algorithm": "EdDSA",
+                "private_key_file": write_ed25519_key(prefix)
+            }
+        ],
+        "jwt_active_kid": format!("{}-old", prefix)
+    })
+}
+
+fn active_kid(client: &rocket::local::blocking::Client) -> String {
+    let response = client
+        .get("/auth/admin/signing-keys")
+        .header(Header::new("admin-key", "admin-secret"))
+        .dispatch();
+    let body: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    body["active_kid"].as_str().unwrap().to_owned()
+}
+
+#[test]
+fn accepts_tokens_from_retired_key_after_promotion() {
+    let client = client_with(keyring_settings("rotation"));
+    create_user(&client, "keyring_rotation");
+
+    let old_token = get_access_token(&login(&client, "keyring_rotation").1);
+    assert_eq!(
+        decode_header(&old_token).unwrap().kid.unwrap(),
+        "rotation-old"
+    );
+
+    let response = client
+        .post("/auth/admin/signing-keys/rotation-new/promote")
+        .header(Header::new("admin-key", "admin-secret"))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+
+    let new_token = get_access_token(&login(&client, "keyring_rotation").1);
+    assert_eq!(
+        decode_header(&new_token).unwrap().kid.unwrap(),
+        "rotation-new"
+    );
+
+    for token in [old_token, new_token].iter() {
+        let response = client
+            .get("/auth/authenticate")
+            .header(Header::new("token", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+    }
+}
+
+#[test]
+fn does_not_promote_without_admin_key() {
+    let client = client_with(keyring_settings("unauthorized"));
+
+    let response = client
+        .post("/auth/admin/signing-keys/unauthorized-new/promote")
+        .header(Header::new("admin-key", "not-the-admin-secret"))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Unauthorized);
+}
+
+#[test]
+fn does_not_promote_unknown_key() {
+    let client = client_with(keyring_settings("unknown"));
+
+    let response = client
+        .post("/auth/admin/signing-keys/missing/promote")
+        .header(Header::new("admin-key", "admin-secret"))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn keeps_promoted_key_active_after_restart() {
+    let client = client_with(keyring_settings("restart"));
+
+    let response = client
+        .post("/auth/admin/signing-keys/restart-new/promote")
+        .header(Header::new("admin-key", "admin-secret"))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let restarted = client_with(keyring_settings("restart"));
+    assert_eq!(active_kid(&restarted), "restart-new");
+}
+
+#[test]
+fn accepts_tokens_without_kid_after_moving_to_keyring() {
+    let client = client_with(json!({}));
+    create_user(&client, "keyring_legacy");
+    let token = get_access_token(&login(&client, "keyring_legacy").1);
+    let claims = super::get_token_claims(&token);
+    let legacy_token = encode(
+        &JwtHeader::new(jsonwebtoken::Algorithm::HS512),
+        &claims,
+        &EncodingKey::from_secret(b"testsecret"),
+    )
+    .unwrap();
+
+    let client = client_with(keyring_settings("legacy"));
+    let response = client
+        .get("/auth/authenticate")
+        .header(Header::new("token", format!("Bearer {}", legacy_token)))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[test]
+fn rejects_duplicate_kids() {
+    let signing_config: SigningConfig = rocket::Config::figment()
+        .merge((
+            "jwt_keys",
+            json!([
+                { "kid": "twice", "secret": "first-secret" },
+                { "kid": "twice", "secret": "second-secret" }
+            ]),
+        ))
+        .extract()
+        .unwrap();
+
+    match JWTConfig::from(&signing_config, "testsecret") {
+        Err(KeyError::DuplicateKid(kid)) => assert_eq!(kid, "twice"),
+        _ => panic!("duplicate kid accepted"),
+    }
+}
@@ -0,0 +1,120 @@
+use super::{client_with, create_user, get_access_token, get_client};
+use rocket::{
+    http::{ContentType, Header, Status},
+    local::blocking::Client,
+};
+use serde_json::{json, Value};
+
+fn change_username(client: &Client, identifier: &str, username: &str) -> Status {
+    let token_response = client
+        .post("/auth/login")
+        .header(ContentType::JSON)
+        .body(json!({ "identifier": identifier, "password": "Ibrahim123123" }).to_string())
+        .dispatch();
+    let access_token = get_access_token(&token_response.into_string());
+
+    client
+        .patch("/auth/profile/username")
+        .header(ContentType::JSON)
+        .header(Header::new("token", format!("Bearer {}", access_token)))
+        .body(json!({ "username": username }).to_string())
+        .dispatch()
+        .status()
+}
+
+fn lookup(client: &Client, username: &str) -> Value {
+    let response = client.get(format!("/auth/users/{}", username)).dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+
+    serde_json::from_str(&response.into_string().unwrap()).unwrap()
+}
+
+#[test]
+fn changes_username_and_resolves_old_name() {
+    let client = get_client();
+    create_user(&client, "rename_old");
+
+    assert_eq!(
+        change_username(&client, "rename_old", "rename_new"),
+        Status::Ok
+    );
+
+    let old_lookup = lookup(&client, "rename_old");
+    assert_eq!(old_lookup["username"], "rename_new");
+    assert_eq!(old_lookup["renamed_from"], "rename_old");
+
+    let new_lookup = lookup(&client, "rename_new");
+    assert_eq!(new_lookup["id"], old_lookup["id"]);
+    assert!(new_lookup.get("renamed_from").is_none());
+
+    let unknown_response = client.get("/auth/users/rename_unknown").dispatch();
+    assert_eq!(unknown_response.status(), Status::NotFound);
+}
+
+#[test]
+fn limits_how_often_username_changes() {
+    let client = get_client();
+    create_user(&client, "rename_limit");
+
+    assert_eq!(
+        change_username(&client, "rename_limit", "rename_limit_2"),
+        Status::Ok
+    );
+    assert_eq!(
+        change_username(&client, "rename_limit_2", "rename_limit_3"),
+        Status::TooManyRequests
+    );
+}
+
+#[test]
+fn holds_old_username_for_previous_owner() {
+    let client = client_with(json!({ "username_change_interval": 0 }));
+    create_user(&client, "rename_hold");
+    create_user(&client, "rename_hold_other");
+
+    assert_eq!(
+        change_username(&client, "rename_hold", "rename_hold_new"),
+        Status::Ok
+    );
+    assert_eq!(
+        change_username(&client, "rename_hold_other", "rename_hold"),
+        Status::Conflict
+    );
+
+    let register_response = client
+        .post("/auth/register")
+        .header(ContentType::JSON)
+        .body(
+            json!({
+                "username": "rename_hold",
+                "email": "rename_hold_third@gmail.com",
+                "password": "Ibrahim123123",
+                "password_repeat": "Ibrahim123123"
+            })
+            .to_string(),
+        )
+        .dispatch();
+    assert_eq!(register_response.status(), Status::Conflict);
+
+    assert_eq!(
+        change_username(&client, "rename_hold_new", "rename_hold"),
+        Status::Ok
+    );
+}
+
+#[test]
+fn does_not_change_to_taken_username() {
+    let client = get_client();
+    create_user(&client, "rename_taken");
+    create_user(&client, "rename_taken_owner");
+
+    assert_eq!(
+        change_username(&client, "rename_taken", "rename_taken_owner"),
+        Status::Conflict
+    );
+    assert_eq!(
+        change_username(&client, "rename_taken", "abc"),
+        Status::UnprocessableEntity
+    );
+}
@@ -1,5 +1,9 @@
-use crate::{database::DbConn, jwt::Claims, repository::user::find};
-use jsonwebtoken::{decode, DecodingKey, Validation};
+use crate::{
+    database::DbConn,
+    jwt::{Claims, TokenUse},
+    repository::{revoked_token::is_revoked, user::find_by_id},
+};
+use ring::constant_time::verify_slices_are_equal;
 use rocket::{
     http::Status,
     request::{FromRequest, Outcome},
@@ -11,26 +15,58 @@ use async_trait::async_trait;
 #[derive(Debug)]
 pub struct AccessToken(pub String);
 
+#[derive(Debug)]
+pub struct AdminKey;
+
+#[derive(Debug)]
+pub struct ServiceClient(pub String);
+
+/// Origin of a browser request, taken from `Origin` or else the `Referer` header.
+#[derive(Debug)]
+pub struct RequestOrigin(pub Option<String>);
+
+impl RequestOrigin {
+    pub fn is_allowed(&self, allowed_origins: &[String]) -> bool {
+        match &self.0 {
+            Some(origin) => allowed_origins.iter().any(|o| o == origin),
+            None => false,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum AccessTokenError {
     Missing,
     Invalid,
 }
 
-pub async fn is_token_valid(
+pub async fn validate_access_token(
     conn: &DbConn,
     token: &str,
-    secret_key: &str,
-    validation: &Validation,
-) -> bool {
-    let decode_key = DecodingKey::from_secret(secret_key.as_ref());
+    jwt_config: &JWTConfig,
+) -> Option<Claims> {
+    let claims = jwt_config.keyring.decode::<Claims>(token).ok()?.claims;
+
+    if is_revoked(conn, claims.jti.to_owned())
+        .await
+        .unwrap_or(true)
+    {
+        return None;
+    }
+
+    match (claims.token_use, claims.user_id()) {
+        (TokenUse::Access, Some(id)) if find_by_id(conn, id).await.is_ok() => Some(claims),
+        _ => None,
+    }
+}
+
+pub async fn is_token_valid(conn: &DbConn, token: &str, jwt_config: &JWTConfig) -> bool {
     let request_token: Vec<&str> = token.split(' ').collect();
 
-    match request_token.starts_with(&["Bearer"]) {
-        true => match decode::<Claims>(request_token[1], &decode_key, validation) {
-            Ok(t) => find(conn, t.claims.sub().to_owned()).await.is_ok(),
-            Err(_) => false,
-        },
+    match request_token[..] {
+        ["Bearer", token] => validate_access_token(conn, token, jwt_config)
+            .await
+            .is_some(),
         _ => false,
     }
 }
@@ -41,22 +77,96 @@ impl<'r> FromRequest<'r> for AccessToken {
 
     async fn from_request(request: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
         let db_conn = request.guard::<DbConn>().await.unwrap();
-        let config = request.rocket().state::<GlobalConfig>().unwrap();
         let jwt_config = request.rocket().state::<JWTConfig>().unwrap();
         let keys: Vec<&str> = request.headers().get("token").collect();
         match keys.len() {
             0 => Outcome::Failure((Status::Unauthorized, AccessTokenError::Missing)),
-            1 if is_token_valid(
-                &db_conn,
-                keys[0],
-                &config.auth_secret_key,
-                &jwt_config.validation,
-            )
-            .await =>
-            {
+            1 if is_token_valid(&db_conn, keys[0], jwt_config).await => {
                 Outcome::Success(AccessToken(keys[0].to_string()))
             }
             _ => Outcome::Failure((Status::Unauthorized, AccessTokenError::Invalid)),
         }
     }
 }
+
+#[async_trait]
+impl<'r> FromRequest<'r> for AdminKey {
+    type Error = AccessTokenError;
+
+    async fn from_request(request: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        let config = request.rocket().state::<GlobalConfig>().unwrap();
+        let keys: Vec<&str> = request.headers().get("admin-key").collect();
+        match (&config.admin_api_key, keys.len()) {
+            (_, 0) => Outcome::Failure((Status::Unauthorized, AccessTokenError::Missing)),
+            (Some(admin_key), 1)
+                if verify_slices_are_equal(admin_key.as_bytes(), keys[0].as_bytes()).is_ok() =>
+            {
+                Outcome::Success(AdminKey)
+            }
+            _ => Outcome::Failure((Status::Unauthorized, AccessTokenError::Invalid)),
+        }
+    }
+}
+
+fn parse_basic_credentials(header: &str) -> Option<(String, String)> {
+    let encoded = header.strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(base64::decode(encoded).ok()?).ok()?;
+    let mut credentials = decoded.splitn(2, ':');
+
+    Some((
+        credentials.next()?.to_owned(),
+        credentials.next()?.to_owned(),
+    ))
+}
+
+#[async_trait]
+impl<'r> FromRequest<'r> for ServiceClient {
+    type Error = AccessTokenError;
+
+    async fn from_request(request: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        let config = request.rocket().state::<GlobalConfig>().unwrap();
+        let credentials = match request.headers().get_one("Authorization") {
+            Some(header) => parse_basic_credentials(header),
+            None => return Outcome::Failure((Status::Unauthorized, AccessTokenError::Missing)),
+        };
+
+        let client = credentials.and_then(|(client_id, client_secret)| {
+            config.service_clients.iter().find(|c| {
+                c.client_id == client_id
+                    && verify_slices_are_equal(c.client_secret.as_bytes(), client_secret.as_bytes())
+                        .is_ok()
+            })
+        });
+
+        match client {
+            Some(c) => Outcome::Success(ServiceClient(c.client_id.to_owned())),
+            None => Outcome::Failure((Status::Unauthorized, AccessTokenError::Invalid)),
+        }
+    }
+}
+
+fn origin_from_referer(referer: &str) -> Option<String> {
+    let scheme_end = referer.find("://")? + 3;
+    let origin_end = referer[scheme_end..]
+        .find('/')
+        .map_or(referer.len(), |i| scheme_end + i);
+
+    Some(referer[..origin_end].to_owned())
+}
+
+#[async_trait]
+impl<'r> FromRequest<'r> for RequestOrigin {
+    type Error = ();
+
+    async fn from_request(request: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        let origin = match request.headers().get_one("Origin") {
+            Some(origin) => Some(origin.to_owned()),
+            None => request
+                .headers()
+                .get_one("Referer")
+                .and_then(origin_from_referer),
+        };
+
+        Outcome::Success(RequestOrigin(origin))
+    }
+}
@@ -0,0 +1,64 @@
+use async_trait::async_trait;
+use rocket::request::{FromRequest, Outcome};
+
+/// Device details recorded with each session.
+#[derive(Debug)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+impl ClientInfo {
+    /// Human readable label such as "Firefox on Linux".
+    pub fn label(&self) -> String {
+        let user_agent = match &self.user_agent {
+            Some(ua) => ua,
+            None => return String::from("Unknown device"),
+        };
+
+        let browser = [
+            ("Edg/", "Edge"),
+            ("OPR/", "Opera"),
+            ("Firefox/", "Firefox"),
+            ("Chrome/", "Chrome"),
+            ("Safari/", "Safari"),
+        ]
+        .iter()
+        .find(|(token, _)| user_agent.contains(token))
+        .map(|(_, name)| *name);
+
+        let os = [
+            ("Android", "Android"),
+            ("iPhone", "iOS"),
+            ("iPad", "iOS"),
+            ("Windows", "Windows"),
+            ("Mac OS X", "macOS"),
+            ("Linux", "Linux"),
+        ]
+        .iter()
+        .find(|(token, _)| user_agent.contains(token))
+        .map(|(_, name)| *name);
+
+        match (browser, os) {
+            (Some(browser), Some(os)) => format!("{} on {}", browser, os),
+            (Some(browser), None) => browser.to_owned(),
+            (None, Some(os)) => os.to_owned(),
+            (None, None) => String::from("Unknown device"),
+        }
+    }
+}
+
+#[async_trait]
+impl<'r> FromRequest<'r> for ClientInfo {
+    type Error = ();
+
+    async fn from_request(request: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(ClientInfo {
+            user_agent: request
+                .headers()
+                .get_one("User-Agent")
+                .map(|ua| ua.chars().take(512).collect()),
+            ip_address: request.client_ip().map(|ip| ip.to_string()),
+        })
+    }
+}
@@ -1,6 +1,8 @@
-use jsonwebtoken::Validation;
+use crate::jwt::{KeyError, KeyRing, SigningKey, LEGACY_KID};
+use jsonwebtoken::Algorithm;
 use rocket::config::SecretKey;
-use serde::Deserialize;
+use serde::{de::Error as _, Deserialize, Deserializer};
+use std::sync::Arc;
 
 #[derive(Deserialize)]
 pub struct GlobalConfig {
@@ -9,24 +11,368 @@ pub struct GlobalConfig {
     pub secret_key: SecretKey,
     pub auth_secret_key: String,
     pub allowed_origins: Vec<String>,
+    pub admin_api_key: Option<String>,
+    #[serde(default)]
+    pub service_clients: Vec<ServiceClientConfig>,
+    pub token_hash_key: Option<String>,
+    /// Refuse logins until the account's email address is verified.
+    #[serde(default)]
+    pub require_verified_email: bool,
+    /// Seconds a user has to wait between username changes.
+    #[serde(default = "default_username_change_interval")]
+    pub username_change_interval: i64,
+    /// Seconds an old username stays reserved for its previous owner.
+    #[serde(default = "default_username_hold_period")]
+    pub username_hold_period: i64,
+    /// Seconds a deleted account can still be restored by logging in.
+    #[serde(default = "default_account_deletion_grace_period")]
+    pub account_deletion_grace_period: i64,
+    /// Seconds an account export can be downloaded after it was requested.
+    #[serde(default = "default_account_export_expiry")]
+    pub account_export_expiry: i64,
 }
 
-pub struct JWTConfig {
-    pub validation: Validation,
+fn default_username_change_interval() -> i64 {
+    30 * 24 * 3600
+}
+
+fn default_username_hold_period() -> i64 {
+    90 * 24 * 3600
+}
+
+fn default_account_deletion_grace_period() -> i64 {
+    30 * 24 * 3600
+}
+
+fn default_account_export_expiry() -> i64 {
+    24 * 3600
+}
+
+impl GlobalConfig {
+    pub fn token_hash_key(&self) -> &str {
+        self.token_hash_key
+            .as_deref()
+            .unwrap_or(&self.auth_secret_key)
+    }
+
+    /// Accounts deleted before this can no longer be restored.
+    pub fn restorable_since(&self) -> chrono::NaiveDateTime {
+        chrono::Utc::now().naive_utc()
+            - chrono::Duration::seconds(self.account_deletion_grace_period)
+    }
 }
 
 #[derive(Deserialize)]
+pub struct ServiceClientConfig {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+#[derive(Deserialize)]
+pub struct SigningKeyConfig {
+    pub kid: String,
+    #[serde(default = "default_jwt_algorithm")]
+    pub algorithm: Algorithm,
+    pub private_key_file: Option<String>,
+    pub secret: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SigningConfig {
+    #[serde(default = "default_jwt_algorithm")]
+    pub jwt_algorithm: Algorithm,
+    pub jwt_private_key_file: Option<String>,
+    #[serde(default)]
+    pub jwt_keys: Vec<SigningKeyConfig>,
+    pub jwt_active_kid: Option<String>,
+    /// Seconds between checks for a key promoted on another instance, 0 disables them.
+    #[serde(default = "default_jwt_key_sync_interval")]
+    pub jwt_key_sync_interval: u64,
+}
+
+fn default_jwt_key_sync_interval() -> u64 {
+    60
+}
+
+fn default_jwt_algorithm() -> Algorithm {
+    Algorithm::HS512
+}
+
+fn load_signing_key(
+    kid: &str,
+    algorithm: Algorithm,
+    private_key_file: Option<&String>,
+    secret: &str,
+) -> Result<SigningKey, KeyError> {
+    match (algorithm, private_key_file) {
+        (Algorithm::HS512, _) => Ok(SigningKey::from_secret(kid, secret)),
+        (algorithm, Some(path)) => SigningKey::from_pem_file(kid, algorithm, path),
+        (algorithm, None) => Err(KeyError::Unsupported(algorithm)),
+    }
+}
+
+pub struct JWTConfig {
+    pub keyring: Arc<KeyRing>,
+    pub key_sync_interval: u64,
+}
+
+impl JWTConfig {
+    /// Without `jwt_keys` the ring holds a single key built from `jwt_algorithm`; HS512 keys
+    /// fall back to `auth_secret_key` when no secret is given. That key stays in the ring
+    /// under `LEGACY_KID` when `jwt_keys` does not configure it, so tokens issued before
+    /// carrying a `kid` remain valid.
+    pub fn from(signing_config: &SigningConfig, auth_secret_key: &str) -> Result<Self, KeyError> {
+        let mut keys = signing_config
+            .jwt_keys
+            .iter()
+            .map(|k| {
+                load_signing_key(
+                    &k.kid,
+                    k.algorithm,
+                    k.private_key_file.as_ref(),
+                    k.secret.as_deref().unwrap_or(auth_secret_key),
+                )
+            })
+            .collect::<Result<Vec<SigningKey>, KeyError>>()?;
+
+        for (i, key) in keys.iter().enumerate() {
+            if keys[..i].iter().any(|k| k.kid == key.kid) {
+                return Err(KeyError::DuplicateKid(key.kid.to_owned()));
+            }
+        }
+
+        if !keys.iter().any(|k| k.kid == LEGACY_KID) {
+            keys.push(load_signing_key(
+                LEGACY_KID,
+                signing_config.jwt_algorithm,
+                signing_config.jwt_private_key_file.as_ref(),
+                auth_secret_key,
+            )?);
+        }
+
+        let active_kid = signing_config
+            .jwt_active_kid
+            .to_owned()
+            .unwrap_or_else(|| keys[0].kid.to_owned());
+
+        Ok(Self {
+            keyring: Arc::new(KeyRing::new(keys, &active_kid)?),
+            key_sync_interval: signing_config.jwt_key_sync_interval,
+        })
+    }
+}
+
+#[derive(Deserialize, Clone)]
 pub struct TwitchConfig {
     pub twitch_client_id: String,
     pub twitch_client_secret: String,
     pub twitch_callback_url: String,
+    /// Base of the OAuth authorize and token endpoints.
+    #[serde(default = "default_twitch_id_url")]
+    pub twitch_id_url: String,
+    /// Base of the Helix API the signed in Twitch user is looked up from.
+    #[serde(default = "default_twitch_api_url")]
+    pub twitch_api_url: String,
+}
+
+fn default_twitch_id_url() -> String {
+    "https://id.twitch.tv/oauth2".to_owned()
+}
+
+fn default_twitch_api_url() -> String {
+    "https://api.twitch.tv/helix".to_owned()
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailTransportKind {
+    #[default]
+    Smtp,
+    /// Writes each message as an `.eml` file into `email_file_dir`, for local development.
+    File,
+    /// Keeps messages in memory so tests can inspect them.
+    Memory,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Upgrades a plain connection with STARTTLS.
+    #[default]
+    Starttls,
+    /// Connects over TLS from the start, usually on port 465.
+    Tls,
+    /// Unencrypted, only for local relays.
+    None,
 }
 
 #[derive(Deserialize)]
 pub struct EmailConfig {
+    #[serde(default)]
     pub email_username: String,
+    #[serde(default)]
     pub email_password: String,
     pub email_enabled: bool,
+    #[serde(default)]
+    pub email_transport: EmailTransportKind,
+    #[serde(default = "default_email_smtp_host")]
+    pub email_smtp_host: String,
+    /// Defaults to the usual port of `email_smtp_tls`.
+    pub email_smtp_port: Option<u16>,
+    #[serde(default)]
+    pub email_smtp_tls: SmtpTls,
+    /// From address of outgoing mail, `email_username` when not set.
+    pub email_from: Option<String>,
+    #[serde(default = "default_email_file_dir")]
+    pub email_file_dir: String,
+    /// Directory with `<template>/subject.txt`, `body.txt` and `body.html` files that
+    /// replace the built in email templates.
+    #[serde(default = "default_email_template_dir")]
+    pub email_template_dir: String,
+    /// Page that takes a `token` query parameter and posts it to `/password/reset`.
+    pub password_reset_url: Option<String>,
+    #[serde(default = "default_password_reset_token_expiry")]
+    pub password_reset_token_expiry: i64,
+    /// Page that takes a `token` query parameter and posts it to `/email/verify`.
+    pub email_verification_url: Option<String>,
+    #[serde(default = "default_email_verification_token_expiry")]
+    pub email_verification_token_expiry: i64,
+    /// Pages that take a `token` query parameter and post it to `/email/change/confirm`
+    /// and `/email/change/cancel`. Change links expire like verification links.
+    pub email_change_confirm_url: Option<String>,
+    pub email_change_cancel_url: Option<String>,
+}
+
+fn default_email_smtp_host() -> String {
+    "smtp.gmail.com".to_owned()
+}
+
+fn default_email_file_dir() -> String {
+    "emails".to_owned()
+}
+
+fn default_email_template_dir() -> String {
+    "templates/email".to_owned()
+}
+
+impl EmailConfig {
+    pub fn sender(&self) -> &str {
+        self.email_from.as_deref().unwrap_or(&self.email_username)
+    }
+
+    pub fn smtp_port(&self) -> u16 {
+        self.email_smtp_port.unwrap_or(match self.email_smtp_tls {
+            SmtpTls::Starttls => 587,
+            SmtpTls::Tls => 465,
+            SmtpTls::None => 25,
+        })
+    }
+}
+
+fn default_password_reset_token_expiry() -> i64 {
+    3600
+}
+
+fn default_email_verification_token_expiry() -> i64 {
+    86400
+}
+
+#[derive(Deserialize, Clone)]
+pub struct CleanupConfig {
+    /// Seconds between runs of the token cleanup job, 0 disables it.
+    #[serde(default = "default_token_cleanup_interval")]
+    pub token_cleanup_interval: u64,
+    #[serde(
+        default = "default_token_cleanup_batch_size",
+        deserialize_with = "batch_size"
+    )]
+    pub token_cleanup_batch_size: i64,
+    /// Seconds between runs of the account purge job, 0 disables it.
+    #[serde(default = "default_account_purge_interval")]
+    pub account_purge_interval: u64,
+    #[serde(
+        default = "default_account_purge_batch_size",
+        deserialize_with = "batch_size"
+    )]
+    pub account_purge_batch_size: i64,
+    /// Seconds a deleted account is kept before it is purged.
+    #[serde(default = "default_account_retention_period")]
+    pub account_retention_period: i64,
+}
+
+/// Batched deletes run until a batch comes back short, which never happens for a batch
+/// size below 1.
+fn batch_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    match i64::deserialize(deserializer)? {
+        size if size > 0 => Ok(size),
+        size => Err(D::Error::custom(format!(
+            "batch size must be positive, got {}",
+            size
+        ))),
+    }
+}
+
+fn default_token_cleanup_interval() -> u64 {
+    3600
+}
+
+fn default_token_cleanup_batch_size() -> i64 {
+    1000
+}
+
+fn default_account_purge_interval() -> u64 {
+    24 * 3600
+}
+
+fn default_account_purge_batch_size() -> i64 {
+    100
+}
+
+fn default_account_retention_period() -> i64 {
+    30 * 24 * 3600
 }
 
 pub const COOKIE_REFRESH_TOKEN_NAME: &str = "refresh_token";
+pub const COOKIE_TWITCH_REFRESH_TOKEN_NAME: &str = "twitch_refresh_token";
+pub const COOKIE_TWITCH_STATE_NAME: &str = "twitch_oauth_state";
+
+#[derive(Deserialize, Clone)]
+pub struct OutboxConfig {
+    /// Seconds between delivery runs of the email outbox, 0 disables delivery.
+    #[serde(default = "default_email_outbox_interval")]
+    pub email_outbox_interval: u64,
+    #[serde(
+        default = "default_email_outbox_batch_size",
+        deserialize_with = "batch_size"
+    )]
+    pub email_outbox_batch_size: i64,
+    /// Failed deliveries are retried until this many attempts, then kept as dead letters.
+    #[serde(default = "default_email_max_attempts")]
+    pub email_max_attempts: i32,
+    /// Seconds before the first retry, doubling with every further attempt.
+    #[serde(default = "default_email_retry_delay")]
+    pub email_retry_delay: i64,
+    /// Seconds a dead email is kept for an admin to retry before it is deleted.
+    #[serde(default = "default_email_dead_letter_retention")]
+    pub email_dead_letter_retention: i64,
+}
+
+fn default_email_outbox_interval() -> u64 {
+    5
+}
+
+fn default_email_outbox_batch_size() -> i64 {
+    50
+}
+
+fn default_email_max_attempts() -> i32 {
+    8
+}
+
+fn default_email_retry_delay() -> i64 {
+    60
+}
+
+fn default_email_dead_letter_retention() -> i64 {
+    7 * 24 * 3600
+}
@@ -0,0 +1,55 @@
+use fluent_langneg::{accepted_languages, negotiate_languages, NegotiationStrategy};
+use unic_langid::LanguageIdentifier;
+
+use crate::i18n::BUILTIN_CATALOGS;
+
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// One of the locales there is a message catalog for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Locale(&'static str);
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale(DEFAULT_LOCALE)
+    }
+}
+
+impl Locale {
+    /// Picks the best supported locale for an `Accept-Language` header.
+    pub fn negotiate(accept_language: Option<&str>) -> Self {
+        let requested = accepted_languages::parse(accept_language.unwrap_or_default());
+        let available: Vec<LanguageIdentifier> = BUILTIN_CATALOGS
+            .iter()
+            .map(|(locale, _)| locale.parse().unwrap())
+            .collect();
+        let default: LanguageIdentifier = DEFAULT_LOCALE.parse().unwrap();
+
+        negotiate_languages(
+            &requested,
+            &available,
+            Some(&default),
+            NegotiationStrategy::Lookup,
+        )
+        .first()
+        .and_then(|negotiated| Locale::parse(&negotiated.to_string()))
+        .unwrap_or_default()
+    }
+
+    /// A supported locale by its exact tag, such as a stored user preference.
+    pub fn parse(locale: &str) -> Option<Self> {
+        BUILTIN_CATALOGS
+            .iter()
+            .find(|(supported, _)| supported.eq_ignore_ascii_case(locale))
+            .map(|(supported, _)| Locale(supported))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        self.0
+    }
+
+    /// A user's stored preference wins over the locale negotiated for the request.
+    pub fn or_preference(self, preference: Option<&str>) -> Self {
+        preference.and_then(Locale::parse).unwrap_or(self)
+    }
+}
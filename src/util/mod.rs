@@ -1,4 +1,9 @@
 pub mod authorization;
+pub mod client_info;
 pub mod globals;
+pub mod locale;
+pub mod outbox_cipher;
+pub mod random;
 pub mod response;
+pub mod token_hash;
 pub mod validator;
@@ -0,0 +1,61 @@
+use rand::Rng;
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN},
+    hmac,
+};
+
+/// Encrypts queued email bodies, so the reset and verification links waiting in the outbox
+/// cannot be read from the database.
+#[derive(Clone)]
+pub struct OutboxCipher {
+    key: [u8; 32],
+}
+
+impl OutboxCipher {
+    /// Derives the encryption key from `secret` instead of using it as is, so the key never
+    /// matches one of its other uses.
+    pub fn new(secret: &str) -> Self {
+        let secret = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+        let mut key = [0; 32];
+        key.copy_from_slice(hmac::sign(&secret, b"email_outbox").as_ref());
+
+        Self { key }
+    }
+
+    fn aead_key(&self) -> LessSafeKey {
+        LessSafeKey::new(UnboundKey::new(&CHACHA20_POLY1305, &self.key).unwrap())
+    }
+
+    /// The random nonce followed by the ciphertext, base64 encoded.
+    pub fn seal(&self, plaintext: &str) -> String {
+        let nonce = rand::thread_rng().gen::<[u8; NONCE_LEN]>();
+        let mut sealed = plaintext.as_bytes().to_vec();
+        self.aead_key()
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut sealed,
+            )
+            .unwrap();
+
+        base64::encode([&nonce[..], &sealed].concat())
+    }
+
+    /// `None` when `sealed` was not sealed with this key or was tampered with.
+    pub fn open(&self, sealed: &str) -> Option<String> {
+        let sealed = base64::decode(sealed).ok()?;
+        if sealed.len() < NONCE_LEN {
+            return None;
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).ok()?;
+        let mut ciphertext = ciphertext.to_vec();
+        let plaintext = self
+            .aead_key()
+            .open_in_place(nonce, Aad::empty(), &mut ciphertext)
+            .ok()?;
+
+        String::from_utf8(plaintext.to_vec()).ok()
+    }
+}
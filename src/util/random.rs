@@ -0,0 +1,17 @@
+use rand::Rng;
+
+/// URL safe random identifier for token ids and families.
+pub fn random_id() -> String {
+    base64::encode_config(
+        rand::thread_rng().gen::<[u8; 16]>(),
+        base64::URL_SAFE_NO_PAD,
+    )
+}
+
+/// URL safe random secret for tokens that are handed out once and stored hashed.
+pub fn random_token() -> String {
+    base64::encode_config(
+        rand::thread_rng().gen::<[u8; 32]>(),
+        base64::URL_SAFE_NO_PAD,
+    )
+}
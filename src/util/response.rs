@@ -6,6 +6,8 @@ use rocket::{
 };
 use serde::{Deserialize, Serialize};
 
+use crate::{i18n::Catalogs, util::locale::Locale};
+
 #[derive(Serialize)]
 pub struct AuthResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -24,6 +26,9 @@ pub struct ErrorResponse {
     pub error_type: Option<ErrorType>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error_codes: Option<Vec<String>>,
+    /// `error_codes` in the language negotiated from `Accept-Language`, in the same order.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub messages: Option<Vec<String>>,
 }
 
 #[derive(Debug)]
@@ -48,6 +53,7 @@ impl Error {
                 let body = ErrorResponse {
                     error_codes: Some(e.0),
                     error_type: Some(e.1),
+                    messages: None,
                 };
                 Self::ErrorWithBody(JsonResponse::new(body, status))
             }
@@ -64,11 +70,25 @@ impl Error {
     }
 }
 
+impl From<rocket_sync_db_pools::diesel::result::Error> for Error {
+    fn from(error: rocket_sync_db_pools::diesel::result::Error) -> Self {
+        crate::routes::users_util::get_auth_error_response(error)
+    }
+}
+
 impl<'r> Responder<'r, 'static> for Error {
     fn respond_to(self, request: &'r Request<'_>) -> rocket::response::Result<'static> {
         match self {
             Error::Error(e) => e.respond_to(&request),
-            Error::ErrorWithBody(e) => {
+            Error::ErrorWithBody(mut e) => {
+                let body = &mut e.json.0;
+                if let (Some(codes), Some(catalogs)) =
+                    (&body.error_codes, request.rocket().state::<Catalogs>())
+                {
+                    let locale = Locale::negotiate(request.headers().get_one("Accept-Language"));
+                    body.messages = Some(catalogs.error_messages(locale, codes));
+                }
+
                 rocket::Response::build_from(e.json.respond_to(&request).unwrap())
                     .status(e.status)
                     .header(ContentType::JSON)
@@ -134,6 +154,8 @@ pub struct TokenResponse {
     access_token: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     expires_in: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    refresh_token: Option<String>,
 }
 
 impl TokenResponse {
@@ -141,6 +163,14 @@ impl TokenResponse {
         Self {
             access_token: Some(access_token),
             expires_in: Some(expires_in),
+            refresh_token: None,
+        }
+    }
+
+    pub fn with_refresh_token(self, refresh_token: String) -> Self {
+        Self {
+            refresh_token: Some(refresh_token),
+            ..self
         }
     }
 }
@@ -0,0 +1,9 @@
+use ring::hmac;
+
+/// Keyed hash stored in place of bearer secrets so a database leak does not hand out sessions.
+pub fn hash_token(token: &str, key: &str) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, key.as_bytes());
+    let tag = hmac::sign(&key, token.as_bytes());
+
+    base64::encode_config(tag.as_ref(), base64::URL_SAFE_NO_PAD)
+}